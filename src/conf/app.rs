@@ -1,14 +1,49 @@
-use serde::de::DeserializeOwned;
+use chrono::{DateTime, Local, Utc};
+use chrono_tz::Tz;
+use conf::validate::{Validate, Validator};
+use cron;
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer};
 use serde_humantime;
+use std;
+use std::cmp;
+use std::path::Path;
 use std::time::Duration;
 use structopt::StructOpt;
+use util::conf::ConfigFormat;
+use util::process::Program;
 
 pub trait ArgConf: StructOpt {
     fn conf(&self) -> &str;
+
+    fn conf_overlays(&self) -> &[String] {
+        &[]
+    }
+
+    fn conf_format(&self) -> Option<ConfigFormat> {
+        None
+    }
+
+    fn env_prefix(&self) -> Option<&str> {
+        None
+    }
 }
 
 pub trait Conf: DeserializeOwned {
     fn general(&self) -> &Config;
+
+    fn validate(&self, v: &mut Validator) {
+        v.validate("general", self.general());
+    }
+
+    // looked up in bin_dirs and PATH at startup
+    fn required_programs(&self) -> Vec<Program> {
+        vec![]
+    }
+
+    // dotted key paths whose env: and file: references are resolved on load
+    fn secret_keys() -> Vec<&'static str> {
+        vec![]
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -17,4 +52,289 @@ pub struct Config {
     pub lock_file: String,
     #[serde(with = "serde_humantime", default)]
     pub repeat_delay: Option<Duration>,
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    // only applies to repeat_delay, scheduled runs keep to their fire times
+    #[serde(default)]
+    pub failure_backoff: Option<BackoffConfig>,
+    #[serde(with = "serde_humantime", default)]
+    pub jitter: Option<Duration>,
+    // searched before PATH when looking up hdfs, kinit, etc.
+    #[serde(default)]
+    pub bin_dirs: Vec<String>,
+}
+
+impl Validate for Config {
+    fn validate(&self, v: &mut Validator) {
+        let lock_dir = Path::new(&self.lock_file).parent();
+
+        match lock_dir {
+            Some(dir) if dir.as_os_str().is_empty() || dir.is_dir() => (),
+            Some(dir) => v.add(
+                "lock_file",
+                format!("parent directory {:?} does not exist", dir),
+            ),
+            None => v.add("lock_file", "must be a file path"),
+        }
+
+        v.check(
+            "repeat_delay",
+            self.repeat_delay.is_none() || self.schedule.is_none(),
+            "cannot be set together with schedule",
+        );
+
+        v.check(
+            "failure_backoff",
+            self.failure_backoff.is_none() || self.repeat_delay.is_some(),
+            "requires repeat_delay to be set",
+        );
+
+        if let Some(ref failure_backoff) = self.failure_backoff {
+            v.validate("failure_backoff", failure_backoff);
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BackoffConfig {
+    pub try_count: u64,
+    pub multiplier: f64,
+    // ceiling for the backed off delay, never below the base delay
+    #[serde(default)]
+    pub max_ms: Option<u64>,
+}
+
+impl BackoffConfig {
+    pub fn delay(&self, base: Duration, consecutive_failures: u64) -> Duration {
+        let exp = cmp::min(consecutive_failures, self.try_count);
+        let exp = cmp::min(exp, std::i32::MAX as u64) as i32;
+
+        let base_ms =
+            base.as_secs() as f64 * 1000.0 + f64::from(base.subsec_millis());
+
+        let max_ms = self.max_ms.unwrap_or(std::u64::MAX);
+        let delay_ms = base_ms * self.multiplier.powi(exp);
+
+        // clamped before the cast since powi may overflow to infinity
+        let delay = if delay_ms < max_ms as f64 {
+            Duration::from_millis(delay_ms as u64)
+        } else {
+            Duration::from_millis(max_ms)
+        };
+
+        cmp::max(base, delay)
+    }
+}
+
+impl Validate for BackoffConfig {
+    fn validate(&self, v: &mut Validator) {
+        v.check("multiplier", self.multiplier >= 1.0, "must be at least 1");
+    }
+}
+
+#[derive(Debug)]
+pub struct Schedule {
+    pub cron: cron::Schedule,
+    pub timezone: Option<Tz>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ScheduleRep {
+    pub cron: String,
+    pub timezone: Option<String>,
+}
+
+impl Schedule {
+    pub fn next_fire_times(
+        &self,
+        after: &DateTime<Utc>,
+        n: usize,
+    ) -> Vec<DateTime<Utc>> {
+        // without an explicit timezone the schedule follows local time
+        match self.timezone {
+            Some(tz) => self
+                .cron
+                .after(&after.with_timezone(&tz))
+                .take(n)
+                .map(|dt| dt.with_timezone(&Utc))
+                .collect(),
+            None => self
+                .cron
+                .after(&after.with_timezone(&Local))
+                .take(n)
+                .map(|dt| dt.with_timezone(&Utc))
+                .collect(),
+        }
+    }
+
+    pub fn delay_until_next(&self) -> Option<Duration> {
+        let now = Utc::now();
+
+        self.next_fire_times(&now, 1)
+            .into_iter()
+            .next()
+            .map(|next| {
+                (next - now)
+                    .to_std()
+                    .unwrap_or_else(|_| Duration::from_secs(0))
+            })
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D>(d: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let rep: ScheduleRep = Deserialize::deserialize(d)?;
+
+        let cron = rep
+            .cron
+            .parse::<cron::Schedule>()
+            .map_err(de::Error::custom)?;
+
+        let timezone = match rep.timezone {
+            Some(ref timezone) => {
+                Some(timezone.parse::<Tz>().map_err(de::Error::custom)?)
+            }
+            None => None,
+        };
+
+        Ok(Schedule { cron, timezone })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+    use toml;
+
+    #[test]
+    fn test_schedule_next_fire_times() {
+        let conf: Result<Config, _> = toml::from_str(
+            r#"
+            lock_file = "/tmp/app.lock"
+
+            [schedule]
+            cron = "0 0 2 * * *"
+            timezone = "Asia/Singapore"
+            "#,
+        );
+
+        assert!(conf.is_ok());
+        let conf = conf.unwrap();
+
+        assert!(conf.schedule.is_some());
+        let schedule = conf.schedule.unwrap();
+
+        let after = Utc.ymd(2026, 10, 17).and_hms(0, 0, 0);
+        let times = schedule.next_fire_times(&after, 2);
+
+        assert_eq!(
+            vec![
+                Utc.ymd(2026, 10, 17).and_hms(18, 0, 0),
+                Utc.ymd(2026, 10, 18).and_hms(18, 0, 0),
+            ],
+            times
+        );
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff = BackoffConfig {
+            try_count: 3,
+            multiplier: 2.0,
+            max_ms: None,
+        };
+
+        let base = Duration::from_secs(10);
+
+        assert_eq!(base, backoff.delay(base, 0));
+        assert_eq!(Duration::from_secs(20), backoff.delay(base, 1));
+        assert_eq!(Duration::from_secs(80), backoff.delay(base, 3));
+
+        // capped at try_count
+        assert_eq!(Duration::from_secs(80), backoff.delay(base, 10));
+
+        let capped = BackoffConfig {
+            try_count: std::u64::MAX,
+            multiplier: 10.0,
+            max_ms: Some(60_000),
+        };
+
+        assert_eq!(Duration::from_secs(60), capped.delay(base, 2));
+        assert_eq!(Duration::from_secs(60), capped.delay(base, 1000));
+
+        // never below the base delay
+        assert_eq!(base, capped.delay(base, 0));
+        assert_eq!(
+            Duration::from_secs(90),
+            capped.delay(Duration::from_secs(90), 3)
+        );
+
+        let uncapped = BackoffConfig {
+            try_count: std::u64::MAX,
+            multiplier: 10.0,
+            max_ms: None,
+        };
+
+        assert_eq!(
+            Duration::from_millis(std::u64::MAX),
+            uncapped.delay(base, std::u64::MAX)
+        );
+    }
+
+    #[test]
+    fn test_backoff_requires_repeat_delay() {
+        let conf: Result<Config, _> = toml::from_str(
+            r#"
+            lock_file = "/tmp/app.lock"
+
+            [schedule]
+            cron = "0 0 2 * * *"
+
+            [failure_backoff]
+            try_count = 3
+            multiplier = 2.0
+            "#,
+        );
+
+        assert!(conf.is_ok());
+
+        let mut v = Validator::new();
+        conf.unwrap().validate(&mut v);
+
+        let fields: Vec<&str> =
+            v.violations().iter().map(|v| v.field.as_str()).collect();
+
+        assert_eq!(vec!["failure_backoff"], fields);
+    }
+
+    #[test]
+    fn test_schedule_invalid() {
+        let invalid_cron: Result<Config, _> = toml::from_str(
+            r#"
+            lock_file = "/tmp/app.lock"
+
+            [schedule]
+            cron = "not a cron"
+            "#,
+        );
+
+        assert!(invalid_cron.is_err());
+
+        let invalid_tz: Result<Config, _> = toml::from_str(
+            r#"
+            lock_file = "/tmp/app.lock"
+
+            [schedule]
+            cron = "0 */15 * * * *"
+            timezone = "Mars/Olympus_Mons"
+            "#,
+        );
+
+        assert!(invalid_tz.is_err());
+    }
 }
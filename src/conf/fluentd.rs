@@ -1,3 +1,5 @@
+use conf::validate::{Validate, Validator};
+
 #[derive(Deserialize, Debug)]
 pub struct Config {
     pub address: String,
@@ -6,3 +8,11 @@ pub struct Config {
     pub multiplier: f64,
     pub store_file_path: Option<String>,
 }
+
+impl Validate for Config {
+    fn validate(&self, v: &mut Validator) {
+        v.check("address", !self.address.is_empty(), "must not be empty");
+        v.check("tag", !self.tag.is_empty(), "must not be empty");
+        v.check("multiplier", self.multiplier > 0.0, "must be positive");
+    }
+}
@@ -2,3 +2,11 @@
 pub struct Config {
     pub path: String,
 }
+
+#[derive(Deserialize, Debug, Default)]
+pub struct MountsConfig {
+    #[serde(default)]
+    pub include_fs_types: Vec<String>,
+    #[serde(default)]
+    pub exclude_fs_types: Vec<String>,
+}
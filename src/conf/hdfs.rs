@@ -1,11 +1,137 @@
+use chrono::{Duration, NaiveDate};
+use conf::validate::{Validate, Validator};
+use regex::Regex;
+
 #[derive(Deserialize, Debug)]
 pub struct DfConfig {
     pub path: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
 pub struct CopyConfig {
     pub path: String,
     pub matches: Vec<String>,
     pub copy_to: String,
+    // skip files recorded as copied with the same size and mtime
+    #[serde(default)]
+    pub incremental: bool,
+    // also recopy recorded files whose local size no longer matches
+    #[serde(default)]
+    pub verify_size: bool,
+    #[serde(default)]
+    pub date_window: Option<DateWindowConfig>,
+    // number of concurrent transfers, one at a time if not set
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    // aggregate cap over all concurrent transfers
+    #[serde(default)]
+    pub max_bytes_per_sec: Option<u64>,
+    // compare each copied file against its HDFS source
+    #[serde(default)]
+    pub verify: Option<VerifyConfig>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct VerifyConfig {
+    // also compare checksums, otherwise only the lengths
+    #[serde(default)]
+    pub checksum: bool,
+    // copy once more if the first copy does not match
+    #[serde(default)]
+    pub retry: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DateWindowConfig {
+    // first capture group holds the date, e.g. dt=(\d{4}-\d{2}-\d{2})
+    pub pattern: String,
+    // chrono format of the captured date, e.g. %Y/%m/%d
+    pub format: String,
+    pub window: DateWindow,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "value")]
+pub enum DateWindow {
+    // today and the days before it
+    LastDays(u32),
+    Yesterday,
+    Range { from: NaiveDate, to: NaiveDate },
+}
+
+impl DateWindow {
+    pub fn is_empty(&self) -> bool {
+        match *self {
+            DateWindow::LastDays(days) => days == 0,
+            DateWindow::Yesterday => false,
+            DateWindow::Range { from, to } => from > to,
+        }
+    }
+
+    // inclusive first and last dates, None for an empty window
+    pub fn bounds(&self, today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+        if self.is_empty() {
+            return None;
+        }
+
+        match *self {
+            DateWindow::LastDays(days) => {
+                Some((today - Duration::days(i64::from(days) - 1), today))
+            }
+            DateWindow::Yesterday => {
+                let yesterday = today - Duration::days(1);
+                Some((yesterday, yesterday))
+            }
+            DateWindow::Range { from, to } => Some((from, to)),
+        }
+    }
+}
+
+impl Validate for DateWindowConfig {
+    fn validate(&self, v: &mut Validator) {
+        match Regex::new(&self.pattern) {
+            Ok(re) => v.check(
+                "pattern",
+                re.captures_len() > 1,
+                "must have a capture group for the date",
+            ),
+            Err(e) => v.add("pattern", e.to_string()),
+        }
+
+        v.check("format", !self.format.is_empty(), "must not be empty");
+
+        v.check(
+            "window",
+            !self.window.is_empty(),
+            "must cover at least one day",
+        );
+    }
+}
+
+impl Validate for CopyConfig {
+    fn validate(&self, v: &mut Validator) {
+        for (i, m) in self.matches.iter().enumerate() {
+            if let Err(e) = Regex::new(m) {
+                v.add(&format!("matches[{}]", i), e.to_string());
+            }
+        }
+
+        v.check("copy_to", !self.copy_to.is_empty(), "must not be empty");
+
+        v.check(
+            "concurrency",
+            self.concurrency != Some(0),
+            "must be at least 1",
+        );
+
+        v.check(
+            "max_bytes_per_sec",
+            self.max_bytes_per_sec != Some(0),
+            "must be at least 1",
+        );
+
+        if let Some(ref date_window) = self.date_window {
+            v.validate("date_window", date_window);
+        }
+    }
 }
@@ -5,3 +5,4 @@ pub mod fs;
 pub mod hdfs;
 pub mod krb5;
 pub mod pg;
+pub mod validate;
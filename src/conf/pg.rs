@@ -1,3 +1,4 @@
+use conf::validate::{Validate, Validator};
 use error::{ErrorKind, Result};
 use failure::ResultExt;
 use filebuffer::FileBuffer;
@@ -13,6 +14,18 @@ pub struct Config {
     pub tls_mode: TlsModeNative,
 }
 
+impl Validate for Config {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            "connection_url",
+            !self.connection_url.is_empty(),
+            "must not be empty",
+        );
+
+        v.check("estimated_cap", self.estimated_cap != 0, "must be non-zero");
+    }
+}
+
 #[derive(Debug)]
 pub enum TlsModeNative {
     None,
@@ -0,0 +1,141 @@
+use error::custom::{ValidationError, Violation};
+
+pub trait Validate {
+    fn validate(&self, v: &mut Validator);
+}
+
+#[derive(Default, Debug)]
+pub struct Validator {
+    prefix: Vec<String>,
+    violations: Vec<Violation>,
+}
+
+impl Validator {
+    pub fn new() -> Validator {
+        Validator::default()
+    }
+
+    fn field_path(&self, field: &str) -> String {
+        let mut path = self.prefix.join(".");
+
+        if !path.is_empty() && !field.is_empty() {
+            path.push('.');
+        }
+
+        path.push_str(field);
+        path
+    }
+
+    pub fn add<S>(&mut self, field: &str, msg: S)
+    where
+        S: Into<String>,
+    {
+        let field = self.field_path(field);
+        self.violations.push(Violation::new(field, msg));
+    }
+
+    pub fn check<S>(&mut self, field: &str, valid: bool, msg: S)
+    where
+        S: Into<String>,
+    {
+        if !valid {
+            self.add(field, msg);
+        }
+    }
+
+    pub fn nested<F>(&mut self, field: &str, f: F)
+    where
+        F: FnOnce(&mut Validator),
+    {
+        self.prefix.push(field.to_owned());
+        f(self);
+        self.prefix.pop();
+    }
+
+    pub fn validate<T>(&mut self, field: &str, value: &T)
+    where
+        T: Validate,
+    {
+        self.nested(field, |v| value.validate(v));
+    }
+
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    pub fn into_result(self) -> Result<(), ValidationError> {
+        if self.violations.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError::new(self.violations))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use conf::{fluentd, hdfs, pg};
+
+    #[test]
+    fn test_validator_field_paths() {
+        let mut v = Validator::new();
+
+        v.check("top", false, "top failed");
+        v.check("ok", true, "never reported");
+
+        v.nested("outer", |v| {
+            v.add("inner", "inner failed");
+            v.nested("deeper", |v| v.add("leaf", "leaf failed"));
+        });
+
+        let fields: Vec<&str> =
+            v.violations().iter().map(|v| v.field.as_str()).collect();
+
+        assert_eq!(vec!["top", "outer.inner", "outer.deeper.leaf"], fields);
+        assert!(v.into_result().is_err());
+    }
+
+    #[test]
+    fn test_validator_empty_ok() {
+        assert!(Validator::new().into_result().is_ok());
+    }
+
+    #[test]
+    fn test_validate_sub_configs() {
+        let fluentd = fluentd::Config {
+            address: "localhost:24224".to_owned(),
+            tag: "app".to_owned(),
+            try_count: 3,
+            multiplier: 0.0,
+            store_file_path: None,
+        };
+
+        let copy = hdfs::CopyConfig {
+            path: "/data".to_owned(),
+            matches: vec![r"^ok$".to_owned(), r"(unclosed".to_owned()],
+            copy_to: "/tmp/data".to_owned(),
+            ..Default::default()
+        };
+
+        let pg = pg::Config {
+            connection_url: "postgres://localhost".to_owned(),
+            estimated_cap: 0,
+            tls_mode: pg::TlsModeNative::None,
+        };
+
+        let mut v = Validator::new();
+        v.validate("fluentd", &fluentd);
+        v.validate("hdfs", &copy);
+        v.validate("pg", &pg);
+
+        let fields: Vec<&str> =
+            v.violations().iter().map(|v| v.field.as_str()).collect();
+
+        assert_eq!(
+            vec!["fluentd.multiplier", "hdfs.matches[1]", "pg.estimated_cap"],
+            fields
+        );
+    }
+}
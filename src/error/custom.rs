@@ -1,13 +1,43 @@
+use chrono::{DateTime, Local};
 use failure::{Backtrace, Fail};
 use regex::Regex;
 use std::fmt::{self, Debug, Display};
 use std::path::PathBuf;
 
-#[derive(Debug, Fail)]
-#[fail(display = "{{ code: {:?}, msg: {} }}", code, msg)]
+#[derive(Debug)]
 pub struct CodeMsgError {
     pub code: Option<i32>,
     pub msg: String,
+    pub cmd: Option<String>,
+    pub signal: Option<i32>,
+}
+
+impl Fail for CodeMsgError {
+    fn cause(&self) -> Option<&Fail> {
+        None
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        None
+    }
+}
+
+impl Display for CodeMsgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{ ")?;
+
+        if let Some(ref cmd) = self.cmd {
+            write!(f, "cmd: {}, ", cmd)?;
+        }
+
+        write!(f, "code: {:?}, ", self.code)?;
+
+        if let Some(signal) = self.signal {
+            write!(f, "signal: {}, ", signal)?;
+        }
+
+        write!(f, "msg: {} }}", self.msg)
+    }
 }
 
 impl CodeMsgError {
@@ -19,6 +49,61 @@ impl CodeMsgError {
         CodeMsgError {
             code: code.into(),
             msg: msg.into(),
+            cmd: None,
+            signal: None,
+        }
+    }
+
+    pub fn with_cmd<S>(mut self, cmd: S) -> CodeMsgError
+    where
+        S: Into<String>,
+    {
+        self.cmd = Some(cmd.into());
+        self
+    }
+
+    pub fn with_signal<S>(mut self, signal: S) -> CodeMsgError
+    where
+        S: Into<Option<i32>>,
+    {
+        self.signal = signal.into();
+        self
+    }
+}
+
+#[derive(Debug, Fail)]
+#[fail(
+    display = "{{ pid: {}, hostname: {}, since: {}, held secs: {}, stale: {} }}",
+    pid,
+    hostname,
+    since,
+    held_secs,
+    stale
+)]
+pub struct LockHeldError {
+    pub pid: u32,
+    pub hostname: String,
+    pub since: DateTime<Local>,
+    pub held_secs: i64,
+    pub stale: bool,
+}
+
+impl LockHeldError {
+    pub fn new<H>(
+        pid: u32,
+        hostname: H,
+        since: DateTime<Local>,
+        stale: bool,
+    ) -> LockHeldError
+    where
+        H: Into<String>,
+    {
+        LockHeldError {
+            pid,
+            hostname: hostname.into(),
+            since,
+            held_secs: (Local::now() - since).num_seconds(),
+            stale,
         }
     }
 }
@@ -79,6 +164,25 @@ impl PermError {
     }
 }
 
+#[derive(Debug, Fail)]
+#[fail(display = "{{ program: {}, searched: {:?} }}", program, searched)]
+pub struct ProgramNotFoundError {
+    pub program: String,
+    pub searched: Vec<PathBuf>,
+}
+
+impl ProgramNotFoundError {
+    pub fn new<P>(program: P, searched: Vec<PathBuf>) -> ProgramNotFoundError
+    where
+        P: Into<String>,
+    {
+        ProgramNotFoundError {
+            program: program.into(),
+            searched,
+        }
+    }
+}
+
 #[derive(Debug, Fail)]
 #[fail(display = "{{ query: {}, inner: {} }}", query, inner)]
 pub struct QueryError<E>
@@ -185,6 +289,61 @@ where
     }
 }
 
+#[derive(Debug)]
+pub struct ValidationError {
+    pub violations: Vec<Violation>,
+}
+
+impl Fail for ValidationError {
+    fn cause(&self) -> Option<&Fail> {
+        None
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        None
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let violations: Vec<String> =
+            self.violations.iter().map(|v| v.to_string()).collect();
+
+        write!(f, "{{ violations: [{}] }}", violations.join(", "))
+    }
+}
+
+impl ValidationError {
+    pub fn new(violations: Vec<Violation>) -> ValidationError {
+        ValidationError { violations }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Violation {
+    pub field: String,
+    pub msg: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.msg)
+    }
+}
+
+impl Violation {
+    pub fn new<F, S>(field: F, msg: S) -> Violation
+    where
+        F: Into<String>,
+        S: Into<String>,
+    {
+        Violation {
+            field: field.into(),
+            msg: msg.into(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ValueError<T>
 where
@@ -251,6 +410,28 @@ mod tests {
         CodeMsgError::new(None, "Fake").context(FakeErrorKind);
     }
 
+    #[test]
+    fn test_code_msg_error_display() {
+        assert_eq!(
+            "{ code: Some(1), msg: Fake }",
+            CodeMsgError::new(1, "Fake").to_string()
+        );
+
+        assert_eq!(
+            "{ cmd: \"kinit\", code: None, signal: 9, msg: Fake }",
+            CodeMsgError::new(None, "Fake")
+                .with_cmd("\"kinit\"")
+                .with_signal(9)
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_lock_held_error_trait() {
+        LockHeldError::new(1, "Fake host", Local::now(), false)
+            .context(FakeErrorKind);
+    }
+
     #[test]
     fn test_msg_error_trait() {
         MsgError::new("Fake").context(FakeErrorKind);
@@ -266,6 +447,12 @@ mod tests {
         PermError::new("Fake perm").context(FakeErrorKind);
     }
 
+    #[test]
+    fn test_program_not_found_error_trait() {
+        ProgramNotFoundError::new("Fake", vec![PathBuf::from("/fake/bin")])
+            .context(FakeErrorKind);
+    }
+
     #[test]
     fn test_query_error_trait() {
         QueryError::new("Fake query", FakeError).context(FakeErrorKind);
@@ -296,6 +483,22 @@ mod tests {
         TargetStringError::new("Fake", FakeError).context(FakeErrorKind);
     }
 
+    #[test]
+    fn test_validation_error_trait() {
+        let e = ValidationError::new(vec![
+            Violation::new("fluentd.multiplier", "must be positive"),
+            Violation::new("pg.estimated_cap", "must be non-zero"),
+        ]);
+
+        assert_eq!(
+            "{ violations: [fluentd.multiplier: must be positive, \
+             pg.estimated_cap: must be non-zero] }",
+            e.to_string()
+        );
+
+        e.context(FakeErrorKind);
+    }
+
     #[test]
     fn test_value_error_trait() {
         ValueError::new("Fake description", 123).context(FakeErrorKind);
@@ -8,9 +8,57 @@ pub mod custom;
 #[cfg_attr(feature = "cargo-clippy", allow(empty_line_after_outer_attr))]
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
 pub enum ErrorKind {
+    #[fail(display = "Unable to rename temporary file into place")]
+    AtomicFileRename,
+
+    #[fail(display = "Unable to write temporary file for atomic write")]
+    AtomicFileWrite,
+
     #[fail(display = "Child output error")]
     ChildOutput,
 
+    #[fail(display = "Command output read error")]
+    CommandOutputRead,
+
+    #[fail(display = "Command spawn error")]
+    CommandSpawn,
+
+    #[fail(display = "Command stdin write error")]
+    CommandStdinWrite,
+
+    #[fail(display = "Command timed out and its process group was killed")]
+    CommandTimeout,
+
+    #[fail(display = "Command wait error")]
+    CommandWait,
+
+    #[fail(display = "Config include directive error")]
+    ConfigInclude,
+
+    #[fail(display = "Config include cycle detected")]
+    ConfigIncludeCycle,
+
+    #[fail(display = "Config validation error")]
+    ConfigValidation,
+
+    #[fail(display = "Config variable cycle detected")]
+    ConfigVarCycle,
+
+    #[fail(display = "Config variable syntax error")]
+    ConfigVarSyntax,
+
+    #[fail(display = "Config variable is undefined")]
+    ConfigVarUndefined,
+
+    #[fail(display = "Unable to parse HDFS copy manifest")]
+    CopyManifestParse,
+
+    #[fail(display = "Unable to build thread pool for parallel copies")]
+    CopyThreadPoolBuild,
+
+    #[fail(display = "Copied file does not match its HDFS source")]
+    CopyVerifyMismatch,
+
     #[fail(display = "CSV entry record parse error")]
     CsvEntryParse,
 
@@ -35,6 +83,9 @@ pub enum ErrorKind {
     #[fail(display = "Directory flag is unexpectedly empty")]
     DirFlagEmpty,
 
+    #[fail(display = "Unable to walk directory")]
+    DirWalk,
+
     #[fail(
         display = "Unable to create directories for copying to destination"
     )]
@@ -64,9 +115,15 @@ pub enum ErrorKind {
     #[fail(display = "Error running hdfs dfs -df command")]
     HdfsDfCmd,
 
+    #[fail(display = "Error invoking hdfs dfs -checksum")]
+    HdfsDfsChecksum,
+
     #[fail(display = "Error invoking hdfs dfs -ls")]
     HdfsDfsLs,
 
+    #[fail(display = "Error invoking hdfs dfs -stat")]
+    HdfsDfsStat,
+
     #[fail(display = "Cannot find hdfs command")]
     HdfsNotAvailable,
 
@@ -79,6 +136,9 @@ pub enum ErrorKind {
     #[fail(display = "Invalid path after glob")]
     InvalidPathAfterGlob,
 
+    #[fail(display = "JSON config parse error")]
+    JsonConfigParse,
+
     #[fail(display = "kinit for username and keytab combi returns error")]
     KinitKeytab,
 
@@ -94,12 +154,33 @@ pub enum ErrorKind {
     #[fail(display = "Lock file exclusive lock error")]
     LockFileExclusiveLock,
 
+    #[fail(display = "Lock file shared lock error")]
+    LockFileSharedLock,
+
+    #[fail(display = "Unable to break stale lock file")]
+    LockFileStaleBreak,
+
+    #[fail(display = "Timed out waiting for lock file")]
+    LockFileWaitTimeout,
+
+    #[fail(display = "Unable to write holder info into lock file")]
+    LockFileWrite,
+
+    #[fail(display = "Cannot parse hdfs dfs -checksum output")]
+    ParseHdfsChecksum,
+
+    #[fail(display = "Cannot parse hdfs dfs -df available value")]
+    ParseHdfsDfAvailableValue,
+
     #[fail(display = "Cannot parse hdfs dfs -df size value")]
     ParseHdfsDfSizeValue,
 
     #[fail(display = "Cannot parse hdfs dfs -df used value")]
     ParseHdfsDfUsedValue,
 
+    #[fail(display = "Cannot parse hdfs dfs -stat output")]
+    ParseHdfsStat,
+
     #[fail(display = "PEM certificate file open error")]
     PemCertificateFileOpen,
 
@@ -130,12 +211,24 @@ pub enum ErrorKind {
     #[fail(display = "Regex minimum capture error")]
     RegexMinCapture,
 
+    #[fail(display = "Environment variable for config secret is missing")]
+    SecretEnvVarMissing,
+
+    #[fail(display = "Unable to read file for config secret")]
+    SecretFileRead,
+
+    #[fail(display = "Unable to register signal handler")]
+    SignalHandlerRegister,
+
     #[fail(display = "Specialized logger initialization error")]
     SpecializedLoggerInit,
 
     #[fail(display = "Error getting statvfs on path")]
     Statvfs,
 
+    #[fail(display = "File system on path reports no blocks")]
+    StatvfsNoBlocks,
+
     #[fail(display = "Conversion from UTF8 stderr to string fail")]
     StderrUtf8Conversion,
 
@@ -168,6 +261,9 @@ pub enum ErrorKind {
 
     #[fail(display = "TOML config parse error")]
     TomlConfigParse,
+
+    #[fail(display = "YAML config parse error")]
+    YamlConfigParse,
 }
 
 #[derive(Debug)]
@@ -0,0 +1,128 @@
+use chrono::{DateTime, Local, NaiveDateTime};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    size: u64,
+    modified: NaiveDateTime,
+    // milliseconds since the epoch, absent in manifests of older versions
+    #[serde(default)]
+    modified_millis: Option<u64>,
+    copied_at: DateTime<Local>,
+}
+
+impl ManifestEntry {
+    pub fn size(&self) -> &u64 {
+        &self.size
+    }
+
+    pub fn modified(&self) -> &NaiveDateTime {
+        &self.modified
+    }
+
+    pub fn modified_millis(&self) -> &Option<u64> {
+        &self.modified_millis
+    }
+
+    pub fn copied_at(&self) -> &DateTime<Local> {
+        &self.copied_at
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CopyManifest {
+    // keyed by HDFS path, sorted so that the file diffs nicely
+    files: BTreeMap<String, ManifestEntry>,
+}
+
+impl CopyManifest {
+    pub fn files(&self) -> &BTreeMap<String, ManifestEntry> {
+        &self.files
+    }
+
+    pub fn get(&self, hdfs_path: &str) -> Option<&ManifestEntry> {
+        self.files.get(hdfs_path)
+    }
+
+    pub fn is_unchanged(
+        &self,
+        hdfs_path: &str,
+        size: u64,
+        modified: &NaiveDateTime,
+    ) -> bool {
+        match self.get(hdfs_path) {
+            Some(entry) => entry.size == size && entry.modified == *modified,
+            None => false,
+        }
+    }
+
+    pub fn record<P>(
+        &mut self,
+        hdfs_path: P,
+        size: u64,
+        modified: NaiveDateTime,
+        modified_millis: Option<u64>,
+    ) where
+        P: Into<String>,
+    {
+        self.files.insert(
+            hdfs_path.into(),
+            ManifestEntry {
+                size,
+                modified,
+                modified_millis,
+                copied_at: Local::now(),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use super::*;
+
+    #[test]
+    fn test_copy_manifest_api() {
+        let modified =
+            NaiveDateTime::parse_from_str("2019-03-01 10:13", "%Y-%m-%d %H:%M")
+                .unwrap();
+
+        let mut manifest = CopyManifest::default();
+        manifest.record("/data/a.csv", 10, modified, Some(1_551_435_180_123));
+
+        assert!(manifest.is_unchanged("/data/a.csv", 10, &modified));
+        assert!(!manifest.is_unchanged("/data/a.csv", 11, &modified));
+        assert!(!manifest.is_unchanged("/data/b.csv", 10, &modified));
+        assert_eq!(10, *manifest.get("/data/a.csv").unwrap().size());
+        assert_eq!(
+            Some(1_551_435_180_123),
+            *manifest.get("/data/a.csv").unwrap().modified_millis()
+        );
+    }
+
+    #[test]
+    fn test_copy_manifest_from_str() {
+        let s = r#"{
+            "files": {
+                "/data/a.csv": {
+                    "size": 10,
+                    "modified": "2019-03-01T10:13:00",
+                    "copied_at": "2019-03-02T08:00:00+00:00"
+                }
+            }
+        }"#;
+
+        let v: Result<CopyManifest, _> = serde_json::from_str(s);
+        assert!(v.is_ok());
+
+        let v = v.unwrap();
+        assert_eq!(1, v.files().len());
+        assert_eq!(10, *v.get("/data/a.csv").unwrap().size());
+        assert_eq!(None, *v.get("/data/a.csv").unwrap().modified_millis());
+
+        // check for Serialize trait
+        assert!(serde_json::to_string(&v).is_ok());
+    }
+}
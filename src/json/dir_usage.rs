@@ -0,0 +1,128 @@
+use chrono::{DateTime, Local};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DirUsage {
+    path: String,
+    depth: usize,
+    apparent_size: u64,
+    allocated_size: u64,
+    file_count: u64,
+    dir_count: u64,
+    datetime: DateTime<Local>,
+}
+
+impl DirUsage {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn depth(&self) -> &usize {
+        &self.depth
+    }
+
+    pub fn apparent_size(&self) -> &u64 {
+        &self.apparent_size
+    }
+
+    pub fn allocated_size(&self) -> &u64 {
+        &self.allocated_size
+    }
+
+    pub fn file_count(&self) -> &u64 {
+        &self.file_count
+    }
+
+    pub fn dir_count(&self) -> &u64 {
+        &self.dir_count
+    }
+
+    pub fn datetime(&self) -> &DateTime<Local> {
+        &self.datetime
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct DirUsageBuilder {
+    pub path: String,
+    pub depth: usize,
+    pub apparent_size: u64,
+    pub allocated_size: u64,
+    pub file_count: u64,
+    pub dir_count: u64,
+}
+
+impl DirUsageBuilder {
+    pub fn path<P: Into<String>>(mut self, path: P) -> DirUsageBuilder {
+        self.path = path.into();
+        self
+    }
+
+    pub fn depth(mut self, depth: usize) -> DirUsageBuilder {
+        self.depth = depth;
+        self
+    }
+
+    pub fn apparent_size(mut self, apparent_size: u64) -> DirUsageBuilder {
+        self.apparent_size = apparent_size;
+        self
+    }
+
+    pub fn allocated_size(mut self, allocated_size: u64) -> DirUsageBuilder {
+        self.allocated_size = allocated_size;
+        self
+    }
+
+    pub fn file_count(mut self, file_count: u64) -> DirUsageBuilder {
+        self.file_count = file_count;
+        self
+    }
+
+    pub fn dir_count(mut self, dir_count: u64) -> DirUsageBuilder {
+        self.dir_count = dir_count;
+        self
+    }
+
+    pub fn build(&self) -> DirUsage {
+        DirUsage {
+            path: self.path.clone(),
+            depth: self.depth,
+            apparent_size: self.apparent_size,
+            allocated_size: self.allocated_size,
+            file_count: self.file_count,
+            dir_count: self.dir_count,
+            datetime: Local::now(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use super::*;
+
+    #[test]
+    fn test_dir_usage_api() {
+        let builder = DirUsageBuilder::default();
+
+        let v = builder
+            .path("/data")
+            .depth(1)
+            .apparent_size(1000)
+            .allocated_size(4096)
+            .file_count(3)
+            .dir_count(1)
+            .build();
+
+        assert_eq!("/data", v.path());
+        assert_eq!(1, *v.depth());
+        assert_eq!(1000, *v.apparent_size());
+        assert_eq!(4096, *v.allocated_size());
+        assert_eq!(3, *v.file_count());
+        assert_eq!(1, *v.dir_count());
+
+        // check for Serialize trait
+        let s = serde_json::to_string(&v);
+        assert!(s.is_ok());
+    }
+}
@@ -1,3 +1,7 @@
+pub mod copy_manifest;
+pub mod dir_usage;
 pub mod storage;
 
+pub use self::copy_manifest::{CopyManifest, ManifestEntry};
+pub use self::dir_usage::{DirUsage, DirUsageBuilder};
 pub use self::storage::{Storage, StorageBuilder};
@@ -8,6 +8,10 @@ pub struct Storage {
     remaining: u64,
     used_prop: f64,
     remaining_prop: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    available: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reserved: Option<u64>,
     datetime: DateTime<Local>,
 }
 
@@ -36,6 +40,14 @@ impl Storage {
         &self.remaining_prop
     }
 
+    pub fn available(&self) -> &Option<u64> {
+        &self.available
+    }
+
+    pub fn reserved(&self) -> &Option<u64> {
+        &self.reserved
+    }
+
     pub fn datetime(&self) -> &DateTime<Local> {
         &self.datetime
     }
@@ -46,6 +58,7 @@ pub struct StorageBuilder {
     pub path: String,
     pub capacity: u64,
     pub used: u64,
+    pub available: Option<u64>,
 }
 
 impl StorageBuilder {
@@ -64,6 +77,11 @@ impl StorageBuilder {
         self
     }
 
+    pub fn available(mut self, available: u64) -> StorageBuilder {
+        self.available = Some(available);
+        self
+    }
+
     pub fn build(&self) -> Storage {
         Storage {
             path: self.path.clone(),
@@ -73,6 +91,11 @@ impl StorageBuilder {
             used_prop: self.used as f64 / self.capacity as f64,
             remaining_prop: (self.capacity - self.used) as f64
                 / self.capacity as f64,
+            available: self.available,
+            // space only usable by privileged users, e.g. ext4 reserved blocks
+            reserved: self.available.map(|available| {
+                (self.capacity - self.used).saturating_sub(available)
+            }),
             datetime: Local::now(),
         }
     }
@@ -107,9 +130,36 @@ mod tests {
             *v.remaining_prop()
         );
 
+        // not set without available
+        assert_eq!(None, *v.available());
+        assert_eq!(None, *v.reserved());
+
         // datetime is automatic
     }
 
+    #[test]
+    fn test_storage_available_reserved() {
+        const CAPACITY: u64 = 1000;
+        const USED: u64 = 400;
+        const AVAILABLE: u64 = 550;
+
+        let builder = StorageBuilder::default();
+
+        let v = builder
+            .path("/")
+            .capacity(CAPACITY)
+            .used(USED)
+            .available(AVAILABLE)
+            .build();
+
+        assert_eq!(Some(AVAILABLE), *v.available());
+        assert_eq!(Some(50), *v.reserved());
+
+        let s = serde_json::to_string(&v);
+        assert!(s.is_ok());
+        assert!(s.unwrap().contains("\"reserved\":50"));
+    }
+
     #[test]
     fn test_storage_from_str() {
         let s = r#"{
@@ -1,6 +1,8 @@
 #![cfg_attr(feature = "cargo-clippy", deny(warnings))]
 
 extern crate chrono;
+extern crate chrono_tz;
+extern crate cron;
 extern crate failure;
 #[macro_use]
 extern crate failure_derive;
@@ -8,15 +10,23 @@ extern crate filebuffer;
 extern crate fruently;
 extern crate fs2;
 #[macro_use]
+extern crate lazy_static;
+extern crate libc;
+#[macro_use]
 extern crate log;
 extern crate log4rs;
 extern crate native_tls;
 extern crate postgres;
+extern crate rand;
+extern crate rayon;
 extern crate regex;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_humantime;
+extern crate serde_json;
+extern crate serde_yaml;
+extern crate signal_hook;
 extern crate simple_logger;
 extern crate structopt;
 extern crate toml;
@@ -1,18 +1,39 @@
-use conf::app::{ArgConf, Conf};
+use conf::app::{self, ArgConf, Conf};
 use conf::fluentd;
-use error::custom::PathError;
+use conf::validate::Validator;
+use error::custom::{MsgError, PathError, TargetStringError};
 use error::{Error, ErrorKind};
 use failure::{Fail, ResultExt};
 use fruently::fluent::Fluent;
 use fruently::forwardable::JsonForwardable;
 use fruently::retry_conf::RetryConf;
 use log4rs;
+use rand::{self, Rng};
 use serde::ser::Serialize;
+use serde::Deserialize;
+use serde_json;
+use serde_yaml;
+use signal_hook;
 use simple_logger;
+use std::cmp;
+use std::env;
 use std::fmt::{Debug, Display};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 use toml;
-use util::fs::read_from_file;
+use toml::value::{Table, Value};
+use util::conf::{
+    apply_env_overrides, interpolate_vars, json_without_nulls, merge_values,
+    resolve_secrets, take_includes, yaml_without_nulls, ConfigFormat,
+    ConfigSource, ConfigSources,
+};
+use util::fs::{lock_file, read_from_file};
+use util::process::require_programs;
+
+const SHUTDOWN_POLL_INTERVAL_MS: u64 = 100;
 
 pub fn create_and_check_fluent<T, K>(
     conf: &fluentd::Config,
@@ -51,7 +72,15 @@ where
     K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
 {
     let arg_conf = A::from_args();
-    let conf: C = read_config_file(arg_conf.conf())?;
+    let conf: C = read_layered_config_file(
+        arg_conf.conf(),
+        arg_conf.conf_overlays(),
+        arg_conf.conf_format(),
+        arg_conf.env_prefix(),
+    )?;
+
+    validate_config(&conf)?;
+    require_programs(&conf.required_programs(), &conf.general().bin_dirs)?;
 
     match conf.general().log_conf_path {
         Some(ref log_conf_path) => {
@@ -65,6 +94,21 @@ where
     Ok(conf)
 }
 
+pub fn validate_config<C, K>(conf: &C) -> Result<(), Error<K>>
+where
+    C: Conf,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let mut validator = Validator::new();
+    conf.validate(&mut validator);
+
+    validator
+        .into_result()
+        .context(ErrorKind::ConfigValidation)?;
+
+    Ok(())
+}
+
 pub fn print_run_status<M, T, K>(res: &Result<T, Error<K>>, success_msg: M)
 where
     M: Display,
@@ -78,17 +122,545 @@ where
     }
 }
 
+pub fn run<F, T, M, K>(
+    conf: &app::Config,
+    success_msg: M,
+    mut job: F,
+) -> Result<(), Error<K>>
+where
+    F: FnMut() -> Result<T, Error<K>>,
+    M: Display,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    // keeps the lock held for as long as the runner lives
+    let _flock = lock_file(&conf.lock_file)?;
+    let shutdown = register_shutdown_flag()?;
+
+    let mut consecutive_failures = 0;
+
+    loop {
+        if let Some(ref schedule) = conf.schedule {
+            if !wait_for_schedule(schedule, conf.jitter, &shutdown) {
+                return Ok(());
+            }
+        }
+
+        let start = Instant::now();
+        let res = job();
+        info!("Run completed in {:?}", start.elapsed());
+        print_run_status(&res, &success_msg);
+
+        if res.is_ok() {
+            consecutive_failures = 0;
+        } else {
+            consecutive_failures += 1;
+        }
+
+        let stop = match (&conf.schedule, conf.repeat_delay) {
+            (&Some(_), _) => shutdown.load(Ordering::SeqCst),
+            (&None, Some(delay)) => {
+                let delay = match conf.failure_backoff {
+                    Some(ref backoff) => {
+                        backoff.delay(delay, consecutive_failures)
+                    }
+                    None => delay,
+                } + random_jitter(conf.jitter);
+
+                info!("Next run scheduled in {:?}", delay);
+
+                shutdown.load(Ordering::SeqCst)
+                    || sleep_unless_shutdown(delay, &shutdown)
+            }
+            (&None, None) => return res.map(|_| ()),
+        };
+
+        if stop {
+            info!("Shutdown requested, stopping runner");
+            return Ok(());
+        }
+    }
+}
+
+fn random_jitter(jitter: Option<Duration>) -> Duration {
+    match jitter {
+        Some(jitter) => {
+            let max_ms =
+                jitter.as_secs() * 1000 + u64::from(jitter.subsec_millis());
+
+            Duration::from_millis(rand::thread_rng().gen_range(0, max_ms + 1))
+        }
+        None => Duration::from_secs(0),
+    }
+}
+
+fn wait_for_schedule(
+    schedule: &app::Schedule,
+    jitter: Option<Duration>,
+    shutdown: &AtomicBool,
+) -> bool {
+    let delay = match schedule.delay_until_next() {
+        Some(delay) => delay + random_jitter(jitter),
+        None => {
+            info!("Schedule has no upcoming fire time, stopping runner");
+            return false;
+        }
+    };
+
+    info!("Next run scheduled in {:?}", delay);
+
+    if sleep_unless_shutdown(delay, shutdown) {
+        info!("Shutdown requested, stopping runner");
+        false
+    } else {
+        true
+    }
+}
+
+fn register_shutdown_flag<K>() -> Result<Arc<AtomicBool>, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let shutdown = Arc::new(AtomicBool::new(false));
+
+    for signal in &[signal_hook::SIGINT, signal_hook::SIGTERM] {
+        signal_hook::flag::register(*signal, Arc::clone(&shutdown))
+            .context(ErrorKind::SignalHandlerRegister)?;
+    }
+
+    Ok(shutdown)
+}
+
+fn sleep_unless_shutdown(delay: Duration, shutdown: &AtomicBool) -> bool {
+    let poll_interval = Duration::from_millis(SHUTDOWN_POLL_INTERVAL_MS);
+    let start = Instant::now();
+
+    loop {
+        if shutdown.load(Ordering::SeqCst) {
+            return true;
+        }
+
+        let elapsed = start.elapsed();
+
+        if elapsed >= delay {
+            return false;
+        }
+
+        thread::sleep(cmp::min(poll_interval, delay - elapsed));
+    }
+}
+
 pub fn read_config_file<P, C, K>(conf_path: P) -> Result<C, Error<K>>
 where
     P: AsRef<Path>,
     C: Conf,
     K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    read_layered_config_file(conf_path, &[] as &[&str], None, None)
+}
+
+pub fn read_config_file_with_format<P, C, K>(
+    conf_path: P,
+    format: ConfigFormat,
+) -> Result<C, Error<K>>
+where
+    P: AsRef<Path>,
+    C: Conf,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    read_layered_config_file(conf_path, &[] as &[&str], Some(format), None)
+}
+
+pub fn read_layered_config_file<P, Q, C, K>(
+    conf_path: P,
+    overlay_paths: &[Q],
+    format: Option<ConfigFormat>,
+    env_prefix: Option<&str>,
+) -> Result<C, Error<K>>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    C: Conf,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
 {
     let conf_path = conf_path.as_ref();
 
-    let config: C = toml::from_str(&read_from_file(conf_path)?)
+    let mut sources = ConfigSources::new();
+    let mut value = read_config_value(conf_path, format)?;
+    let base_source = ConfigSource::File(conf_path.to_owned());
+    sources.record("", &value, &base_source);
+
+    for overlay_path in overlay_paths {
+        let overlay_path = overlay_path.as_ref();
+        let overlay = read_config_value(overlay_path, format)?;
+
+        let overlay_source = ConfigSource::File(overlay_path.to_owned());
+        sources.record("", &overlay, &overlay_source);
+        merge_values(&mut value, overlay);
+    }
+
+    // overlays may use and override the vars of the files below them
+    interpolate_vars(&mut value, conf_path, &sources)?;
+
+    if let Some(env_prefix) = env_prefix {
+        apply_env_overrides(&mut value, env_prefix, env::vars(), &mut sources)
+            .context(ErrorKind::TomlConfigParse)?;
+    }
+
+    resolve_secrets(&mut value, &C::secret_keys())?;
+
+    let err = match value.try_into() {
+        Ok(config) => return Ok(config),
+        Err(err) => err,
+    };
+
+    // keys missing altogether have no leaf source and blame the base file
+    let source = error_key_path(&err)
+        .and_then(|key_path| sources.source_of(&key_path))
+        .unwrap_or(&base_source);
+
+    match *source {
+        ConfigSource::File(ref path) => {
+            let format =
+                format.unwrap_or_else(|| ConfigFormat::from_path(path));
+            Err(PathError::new(path, err)
+                .context(format.parse_error_kind())
+                .into())
+        }
+        // environment values are parsed as TOML
+        ConfigSource::Env(ref var_name) => {
+            Err(TargetStringError::new(var_name.as_str(), err)
+                .context(ErrorKind::TomlConfigParse)
+                .into())
+        }
+    }
+}
+
+// toml 0.4 only exposes the failing key through the message, e.g.
+// "invalid type: ... for key `pg.estimated_cap`"
+fn error_key_path(err: &toml::de::Error) -> Option<String> {
+    const KEY_MARKER: &str = " for key `";
+
+    let msg = err.to_string();
+    let start = msg.rfind(KEY_MARKER)? + KEY_MARKER.len();
+    let len = msg[start..].find('`')?;
+
+    Some(msg[start..start + len].to_owned())
+}
+
+fn read_config_value<P, K>(
+    conf_path: P,
+    format: Option<ConfigFormat>,
+) -> Result<Value, Error<K>>
+where
+    P: AsRef<Path>,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    read_included_config_value(conf_path.as_ref(), format, &mut Vec::new())
+}
+
+fn read_included_config_value<K>(
+    conf_path: &Path,
+    format: Option<ConfigFormat>,
+    include_stack: &mut Vec<PathBuf>,
+) -> Result<Value, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let canonical_path = conf_path
+        .canonicalize()
+        .unwrap_or_else(|_| conf_path.to_owned());
+
+    if include_stack.contains(&canonical_path) {
+        let chain: Vec<String> = include_stack
+            .iter()
+            .chain(Some(&canonical_path))
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect();
+
+        Err(PathError::new(
+            conf_path,
+            MsgError::new(format!("Include cycle: {}", chain.join(" -> "))),
+        ))
+        .context(ErrorKind::ConfigIncludeCycle)?;
+    }
+
+    let format = format.unwrap_or_else(|| ConfigFormat::from_path(conf_path));
+    let mut value = parse_config_file(conf_path, format)?;
+
+    let includes = take_includes(&mut value)
         .map_err(|e| PathError::new(conf_path, e))
-        .context(ErrorKind::TomlConfigParse)?;
+        .context(ErrorKind::ConfigInclude)?;
+
+    if includes.is_empty() {
+        return Ok(value);
+    }
+
+    // included files form the base that the including file overrides
+    let include_dir = conf_path.parent().unwrap_or_else(|| Path::new(""));
+    let mut merged = Value::Table(Table::new());
+
+    include_stack.push(canonical_path);
+
+    for include in includes {
+        let include_path = include_dir.join(include);
+
+        merge_values(
+            &mut merged,
+            read_included_config_value(&include_path, None, include_stack)?,
+        );
+    }
+
+    include_stack.pop();
+    merge_values(&mut merged, value);
+
+    Ok(merged)
+}
+
+fn parse_config_file<K>(
+    conf_path: &Path,
+    format: ConfigFormat,
+) -> Result<Value, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let content = read_from_file(conf_path)?;
+
+    let value = match format {
+        ConfigFormat::Json => serde_json::from_str(&content)
+            .map(json_without_nulls)
+            .and_then(Value::deserialize)
+            .map_err(|e| PathError::new(conf_path, e))
+            .context(ErrorKind::JsonConfigParse)?,
+        ConfigFormat::Toml => content
+            .parse::<Value>()
+            .map_err(|e| PathError::new(conf_path, e))
+            .context(ErrorKind::TomlConfigParse)?,
+        ConfigFormat::Yaml => serde_yaml::from_str(&content)
+            .map(yaml_without_nulls)
+            .and_then(Value::deserialize)
+            .map_err(|e| PathError::new(conf_path, e))
+            .context(ErrorKind::YamlConfigParse)?,
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use conf::app::Config;
+    use error;
+    use std::fs;
+    use util::test_util::TempDir;
+
+    #[derive(Deserialize, Debug)]
+    struct FakeConf {
+        general: Config,
+        name: String,
+    }
+
+    impl Conf for FakeConf {
+        fn general(&self) -> &Config {
+            &self.general
+        }
+    }
+
+    #[test]
+    fn test_read_config_file_include_and_vars() {
+        let dir = TempDir::new("include_and_vars");
+
+        let common = r#"
+            [vars]
+            base_dir = "/var/app"
+
+            [general]
+            lock_file = "${base_dir}/app.lock"
+            "#;
+
+        let app = r#"
+            include = ["common.toml"]
+            name = "app at ${base_dir}, literal $${base_dir}"
+            "#;
+
+        assert!(fs::write(dir.join("common.toml"), common).is_ok());
+        assert!(fs::write(dir.join("app.toml"), app).is_ok());
+
+        let conf: error::Result<FakeConf> =
+            read_config_file(dir.join("app.toml"));
+
+        assert!(conf.is_ok());
 
-    Ok(config)
+        let conf = conf.unwrap();
+        assert_eq!("/var/app/app.lock", conf.general.lock_file);
+        assert_eq!("app at /var/app, literal ${base_dir}", conf.name);
+    }
+
+    #[test]
+    fn test_read_config_file_nulls() {
+        let dir = TempDir::new("nulls");
+
+        let json = r#"{
+            "name": "app",
+            "general": {"lock_file": "/tmp/app.lock", "log_conf_path": null}
+        }"#;
+
+        let yaml = "\
+name: app
+general:
+  lock_file: /tmp/app.lock
+  log_conf_path: ~
+";
+
+        assert!(fs::write(dir.join("app.json"), json).is_ok());
+        assert!(fs::write(dir.join("app.yaml"), yaml).is_ok());
+
+        let json_conf: error::Result<FakeConf> =
+            read_config_file(dir.join("app.json"));
+
+        let yaml_conf: error::Result<FakeConf> =
+            read_config_file(dir.join("app.yaml"));
+
+        assert!(json_conf.is_ok());
+        assert_eq!(None, json_conf.unwrap().general.log_conf_path);
+
+        assert!(yaml_conf.is_ok());
+        assert_eq!(None, yaml_conf.unwrap().general.log_conf_path);
+    }
+
+    #[test]
+    fn test_read_config_file_include_cycle() {
+        let dir = TempDir::new("include_cycle");
+
+        assert!(fs::write(dir.join("a.toml"), "include = [\"b.toml\"]").is_ok());
+        assert!(fs::write(dir.join("b.toml"), "include = [\"a.toml\"]").is_ok());
+
+        let conf: error::Result<FakeConf> =
+            read_config_file(dir.join("a.toml"));
+
+        assert!(conf.is_err());
+        assert_eq!(
+            ErrorKind::ConfigIncludeCycle,
+            *conf.unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_read_layered_config_file_error_layer() {
+        let dir = TempDir::new("layered_error_layer");
+        let base = "name = \"app\"\n[general]\nlock_file = \"/tmp/app.lock\"";
+
+        assert!(fs::write(dir.join("app.toml"), base).is_ok());
+        assert!(fs::write(dir.join("app.prod.toml"), "name = 1").is_ok());
+
+        let conf: error::Result<FakeConf> = read_layered_config_file(
+            dir.join("app.toml"),
+            &[dir.join("app.prod.toml")],
+            None,
+            None,
+        );
+
+        assert!(conf.is_err());
+
+        let err = conf.unwrap_err();
+        assert_eq!(ErrorKind::TomlConfigParse, *err.inner.get_context());
+        assert!(err.to_string().contains("app.prod.toml"));
+        assert!(err.to_string().contains("`name`"));
+
+        env::set_var("MEGA_COLL_TEST_LAYERED__GENERAL__LOCK_FILE", "[1]");
+
+        let conf: error::Result<FakeConf> = read_layered_config_file(
+            dir.join("app.toml"),
+            &[] as &[&str],
+            None,
+            Some("MEGA_COLL_TEST_LAYERED"),
+        );
+
+        env::remove_var("MEGA_COLL_TEST_LAYERED__GENERAL__LOCK_FILE");
+        assert!(conf.is_err());
+
+        let err = conf.unwrap_err().to_string();
+        assert!(err.contains("MEGA_COLL_TEST_LAYERED__GENERAL__LOCK_FILE"));
+        assert!(err.contains("`general.lock_file`"));
+    }
+
+    #[test]
+    fn test_read_layered_config_file_vars() {
+        let dir = TempDir::new("layered_vars");
+
+        let base = r#"
+            name = "app"
+
+            [vars]
+            base_dir = "/var/app"
+            env = "dev"
+
+            [general]
+            lock_file = "${base_dir}/${env}.lock"
+            "#;
+
+        let prod = r#"
+            name = "app in ${env} at ${base_dir}"
+
+            [vars]
+            env = "prod"
+            "#;
+
+        assert!(fs::write(dir.join("app.toml"), base).is_ok());
+        assert!(fs::write(dir.join("app.prod.toml"), prod).is_ok());
+        assert!(
+            fs::write(dir.join("app.bad.toml"), "name = \"${nope}\"").is_ok()
+        );
+
+        let conf: error::Result<FakeConf> = read_layered_config_file(
+            dir.join("app.toml"),
+            &[dir.join("app.prod.toml")],
+            None,
+            None,
+        );
+
+        let bad_conf: error::Result<FakeConf> = read_layered_config_file(
+            dir.join("app.toml"),
+            &[dir.join("app.bad.toml")],
+            None,
+            None,
+        );
+
+        assert!(conf.is_ok());
+
+        let conf = conf.unwrap();
+        assert_eq!("/var/app/prod.lock", conf.general.lock_file);
+        assert_eq!("app in prod at /var/app", conf.name);
+
+        assert!(bad_conf.is_err());
+
+        let err = bad_conf.unwrap_err();
+        assert_eq!(ErrorKind::ConfigVarUndefined, *err.inner.get_context());
+        assert!(err.to_string().contains("app.bad.toml"));
+    }
+
+    #[test]
+    fn test_run_once_without_repeat_delay() {
+        let dir = TempDir::new("run_once");
+
+        let conf = Config {
+            log_conf_path: None,
+            lock_file: dir.join("app.lock").to_string_lossy().into_owned(),
+            repeat_delay: None,
+            schedule: None,
+            failure_backoff: None,
+            jitter: None,
+            bin_dirs: vec![],
+        };
+
+        let mut count = 0;
+
+        let res: error::Result<()> = run(&conf, "Done", || {
+            count += 1;
+            Ok(())
+        });
+
+        assert!(res.is_ok());
+        assert_eq!(1, count);
+    }
 }
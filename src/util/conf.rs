@@ -0,0 +1,961 @@
+use error::custom::{MsgError, PathError, TargetStringError};
+use error::{Error, ErrorKind};
+use failure::{Fail, ResultExt};
+use serde_json;
+use serde_yaml;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::value::{Table, Value};
+
+const ENV_KEY_SEP: &str = "__";
+const SECRET_ENV_PREFIX: &str = "env:";
+const SECRET_FILE_PREFIX: &str = "file:";
+const INCLUDE_KEY: &str = "include";
+const VARS_KEY: &str = "vars";
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> ConfigFormat {
+        let ext = path
+            .as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+
+        // anything unrecognized stays as TOML for backward compatibility
+        match ext.as_ref().map(String::as_str) {
+            Some("json") => ConfigFormat::Json,
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    pub fn parse_error_kind(self) -> ErrorKind {
+        match self {
+            ConfigFormat::Json => ErrorKind::JsonConfigParse,
+            ConfigFormat::Toml => ErrorKind::TomlConfigParse,
+            ConfigFormat::Yaml => ErrorKind::YamlConfigParse,
+        }
+    }
+}
+
+// TOML has no null, so the nulls that generated JSON and YAML configs emit
+// for unset Option fields are dropped and read as absent keys instead
+pub fn json_without_nulls(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => serde_json::Value::Object(
+            map.into_iter()
+                .filter(|&(_, ref v)| !v.is_null())
+                .map(|(k, v)| (k, json_without_nulls(v)))
+                .collect(),
+        ),
+        serde_json::Value::Array(values) => serde_json::Value::Array(
+            values.into_iter().map(json_without_nulls).collect(),
+        ),
+        value => value,
+    }
+}
+
+pub fn yaml_without_nulls(value: serde_yaml::Value) -> serde_yaml::Value {
+    match value {
+        serde_yaml::Value::Mapping(map) => serde_yaml::Value::Mapping(
+            map.into_iter()
+                .filter(|&(_, ref v)| !v.is_null())
+                .map(|(k, v)| (k, yaml_without_nulls(v)))
+                .collect(),
+        ),
+        serde_yaml::Value::Sequence(values) => serde_yaml::Value::Sequence(
+            values.into_iter().map(yaml_without_nulls).collect(),
+        ),
+        value => value,
+    }
+}
+
+pub fn merge_values(base: &mut Value, overlay: Value) {
+    match overlay {
+        Value::Table(overlay_table) => {
+            if let Value::Table(ref mut base_table) = *base {
+                merge_tables(base_table, overlay_table);
+                return;
+            }
+
+            *base = Value::Table(overlay_table);
+        }
+        overlay => *base = overlay,
+    }
+}
+
+fn merge_tables(base: &mut Table, overlay: Table) {
+    for (key, overlay_value) in overlay {
+        let merged = match base.get_mut(&key) {
+            Some(base_value) => {
+                merge_values(base_value, overlay_value);
+                None
+            }
+            None => Some(overlay_value),
+        };
+
+        if let Some(value) = merged {
+            base.insert(key, value);
+        }
+    }
+}
+
+pub fn env_var_key_path(prefix: &str, var_name: &str) -> Option<Vec<String>> {
+    let full_prefix = format!("{}{}", prefix, ENV_KEY_SEP);
+
+    if !var_name.starts_with(&full_prefix) {
+        return None;
+    }
+
+    let keys: Vec<String> = var_name[full_prefix.len()..]
+        .split(ENV_KEY_SEP)
+        .map(|key| key.to_lowercase())
+        .collect();
+
+    if keys.iter().any(|key| key.is_empty()) {
+        None
+    } else {
+        Some(keys)
+    }
+}
+
+pub fn parse_env_value(raw: &str) -> Value {
+    // allows numbers, booleans and arrays to be overridden with their proper
+    // types, and falls back to a plain string for everything else
+    format!("value = {}", raw)
+        .parse::<Value>()
+        .ok()
+        .and_then(|mut v| {
+            v.as_table_mut().and_then(|table| table.remove("value"))
+        })
+        .unwrap_or_else(|| Value::String(raw.to_owned()))
+}
+
+pub fn set_key_path(
+    root: &mut Value,
+    keys: &[String],
+    value: Value,
+) -> Result<(), MsgError> {
+    let (last, parents) = match keys.split_last() {
+        Some(split) => split,
+        None => return Err(MsgError::new("Empty key path")),
+    };
+
+    let mut table = match *root {
+        Value::Table(ref mut table) => table,
+        _ => return Err(MsgError::new("Config root is not a table")),
+    };
+
+    for (i, key) in parents.iter().enumerate() {
+        let child = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Table(Table::new()));
+
+        table = match *child {
+            Value::Table(ref mut table) => table,
+            _ => {
+                return Err(MsgError::new(format!(
+                    "Key `{}` is not a table",
+                    keys[..=i].join(".")
+                )))
+            }
+        };
+    }
+
+    table.insert(last.clone(), value);
+    Ok(())
+}
+
+pub fn apply_env_overrides<I>(
+    root: &mut Value,
+    prefix: &str,
+    vars: I,
+    sources: &mut ConfigSources,
+) -> Result<(), TargetStringError<MsgError>>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut vars: Vec<(String, String)> = vars.into_iter().collect();
+
+    // keeps the override order deterministic regardless of the environment
+    vars.sort();
+
+    for (var_name, raw) in vars {
+        if let Some(keys) = env_var_key_path(prefix, &var_name) {
+            let value = parse_env_value(&raw);
+            let source = ConfigSource::Env(var_name.clone());
+            sources.record(&keys.join("."), &value, &source);
+
+            set_key_path(root, &keys, value)
+                .map_err(|e| TargetStringError::new(var_name.as_str(), e))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigSource {
+    File(PathBuf),
+    Env(String),
+}
+
+// the layer that last set each leaf key, so that a value failing to
+// deserialize can be traced back to its file or environment variable
+#[derive(Debug, Default)]
+pub struct ConfigSources {
+    leaves: BTreeMap<String, ConfigSource>,
+}
+
+impl ConfigSources {
+    pub fn new() -> ConfigSources {
+        ConfigSources::default()
+    }
+
+    pub fn record(
+        &mut self,
+        key_path: &str,
+        value: &Value,
+        source: &ConfigSource,
+    ) {
+        match *value {
+            Value::Table(ref table) => {
+                for (key, v) in table {
+                    self.record(&join_key_path(key_path, key), v, source);
+                }
+            }
+            _ => {
+                // a scalar replacing a table hides everything set below it
+                let nested = format!("{}.", key_path);
+                self.leaves.retain(|k, _| !k.starts_with(&nested));
+
+                self.leaves.insert(key_path.to_owned(), source.clone());
+            }
+        }
+    }
+
+    // the leaf at the key path or the closest one above it
+    pub fn source_of(&self, key_path: &str) -> Option<&ConfigSource> {
+        let mut key_path = key_path;
+
+        loop {
+            if let Some(source) = self.leaves.get(key_path) {
+                return Some(source);
+            }
+
+            match key_path.rfind('.') {
+                Some(i) => key_path = &key_path[..i],
+                None => return None,
+            }
+        }
+    }
+}
+
+fn join_key_path(key_path: &str, key: &str) -> String {
+    if key_path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", key_path, key)
+    }
+}
+
+pub fn resolve_secret<K>(
+    raw: &str,
+    key_path: &str,
+) -> Result<Option<String>, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    if raw.starts_with(SECRET_ENV_PREFIX) {
+        let var_name = &raw[SECRET_ENV_PREFIX.len()..];
+
+        let secret = env::var(var_name)
+            .map_err(|e| TargetStringError::new(var_name, e))
+            .map_err(|e| TargetStringError::new(key_path, e))
+            .context(ErrorKind::SecretEnvVarMissing)?;
+
+        Ok(Some(secret))
+    } else if raw.starts_with(SECRET_FILE_PREFIX) {
+        let file_path = &raw[SECRET_FILE_PREFIX.len()..];
+
+        let secret = fs::read_to_string(file_path)
+            .map_err(|e| PathError::new(file_path, e))
+            .map_err(|e| TargetStringError::new(key_path, e))
+            .context(ErrorKind::SecretFileRead)?;
+
+        Ok(Some(secret.trim().to_owned()))
+    } else {
+        Ok(None)
+    }
+}
+
+// only the given keys are resolved so that other values, e.g. file:// URIs,
+// are never mistaken for secret references
+pub fn resolve_secrets<K>(
+    value: &mut Value,
+    secret_keys: &[&str],
+) -> Result<(), Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    for key_path in secret_keys {
+        if let Some(&mut Value::String(ref mut s)) =
+            key_path_mut(value, key_path)
+        {
+            if let Some(secret) = resolve_secret(s, key_path)? {
+                *s = secret;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn key_path_mut<'a>(
+    value: &'a mut Value,
+    key_path: &str,
+) -> Option<&'a mut Value> {
+    key_path
+        .split('.')
+        .fold(Some(value), |value, key| match value {
+            Some(value) => match *value {
+                Value::Table(ref mut table) => table.get_mut(key),
+                _ => None,
+            },
+            None => None,
+        })
+}
+
+fn visit_strings<F, E>(
+    value: &mut Value,
+    key_path: &str,
+    f: &mut F,
+) -> Result<(), E>
+where
+    F: FnMut(&mut String, &str) -> Result<(), E>,
+{
+    match *value {
+        Value::String(ref mut s) => f(s, key_path)?,
+        Value::Array(ref mut values) => {
+            for (i, v) in values.iter_mut().enumerate() {
+                visit_strings(v, &format!("{}[{}]", key_path, i), f)?;
+            }
+        }
+        Value::Table(ref mut table) => {
+            for (key, v) in table.iter_mut() {
+                visit_strings(v, &join_key_path(key_path, key), f)?;
+            }
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+pub fn take_includes(value: &mut Value) -> Result<Vec<String>, MsgError> {
+    let include = match value.as_table_mut() {
+        Some(table) => table.remove(INCLUDE_KEY),
+        None => None,
+    };
+
+    match include {
+        None => Ok(vec![]),
+        Some(Value::String(path)) => Ok(vec![path]),
+        Some(Value::Array(paths)) => paths
+            .into_iter()
+            .map(|path| match path {
+                Value::String(path) => Ok(path),
+                other => Err(MsgError::new(format!(
+                    "`{}` entries must be strings, found {}",
+                    INCLUDE_KEY,
+                    other.type_str()
+                ))),
+            })
+            .collect(),
+        Some(other) => Err(MsgError::new(format!(
+            "`{}` must be a string or an array of strings, found {}",
+            INCLUDE_KEY,
+            other.type_str()
+        ))),
+    }
+}
+
+// errors blame the file that set the offending key, else conf_path
+pub fn interpolate_vars<P, K>(
+    value: &mut Value,
+    conf_path: P,
+    sources: &ConfigSources,
+) -> Result<(), Error<K>>
+where
+    P: AsRef<Path>,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let conf_path = conf_path.as_ref();
+
+    let vars = match value.as_table_mut().and_then(|t| t.remove(VARS_KEY)) {
+        None => Table::new(),
+        Some(Value::Table(vars)) => vars,
+        Some(other) => {
+            return Err(MsgError::new(format!(
+                "`{}` must be a table, found {}",
+                VARS_KEY,
+                other.type_str()
+            )))
+            .map_err(|e| PathError::new(conf_path, e))
+            .context(ErrorKind::ConfigVarSyntax)?
+        }
+    };
+
+    let mut resolver = VarResolver {
+        conf_path,
+        sources,
+        vars,
+        resolved: BTreeMap::new(),
+        stack: vec![],
+    };
+
+    visit_strings(value, "", &mut |s, key_path| {
+        *s = resolver.interpolate(s, key_path)?;
+        Ok(())
+    })
+}
+
+struct VarResolver<'a> {
+    conf_path: &'a Path,
+    sources: &'a ConfigSources,
+    vars: Table,
+    resolved: BTreeMap<String, String>,
+    stack: Vec<String>,
+}
+
+impl<'a> VarResolver<'a> {
+    fn fail<T, S, K>(
+        &self,
+        key_path: &str,
+        msg: S,
+        kind: ErrorKind,
+    ) -> Result<T, Error<K>>
+    where
+        S: Into<String>,
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+    {
+        let conf_path = match self.sources.source_of(key_path) {
+            Some(&ConfigSource::File(ref path)) => path.as_path(),
+            _ => self.conf_path,
+        };
+
+        Err(PathError::new(conf_path, MsgError::new(msg))).context(kind)?
+    }
+
+    fn lookup<K>(
+        &mut self,
+        name: &str,
+        key_path: &str,
+    ) -> Result<String, Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+    {
+        if let Some(value) = self.resolved.get(name) {
+            return Ok(value.clone());
+        }
+
+        if self.stack.iter().any(|n| n == name) {
+            let mut chain = self.stack.clone();
+            chain.push(name.to_owned());
+
+            return self.fail(
+                key_path,
+                format!("Variable cycle: {}", chain.join(" -> ")),
+                ErrorKind::ConfigVarCycle,
+            );
+        }
+
+        // variables in the file take precedence over the environment
+        let raw = match self.vars.get(name) {
+            Some(&Value::String(ref v)) => v.clone(),
+            Some(&Value::Integer(v)) => v.to_string(),
+            Some(&Value::Float(v)) => v.to_string(),
+            Some(&Value::Boolean(v)) => v.to_string(),
+            Some(other) => {
+                return self.fail(
+                    &join_key_path(VARS_KEY, name),
+                    format!(
+                        "Variable `{}` must be a string, number or boolean, \
+                         found {}",
+                        name,
+                        other.type_str()
+                    ),
+                    ErrorKind::ConfigVarSyntax,
+                )
+            }
+            None => {
+                return env::var(name).or_else(|_| {
+                    self.fail(
+                        key_path,
+                        format!(
+                            "Undefined variable `{}` referenced in `{}`",
+                            name, key_path
+                        ),
+                        ErrorKind::ConfigVarUndefined,
+                    )
+                })
+            }
+        };
+
+        self.stack.push(name.to_owned());
+        let value = self.interpolate(&raw, &join_key_path(VARS_KEY, name));
+        self.stack.pop();
+
+        let value = value?;
+        self.resolved.insert(name.to_owned(), value.clone());
+        Ok(value)
+    }
+
+    fn interpolate<K>(
+        &mut self,
+        s: &str,
+        key_path: &str,
+    ) -> Result<String, Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+    {
+        let mut out = String::with_capacity(s.len());
+        let mut rest = s;
+
+        while let Some(start) = rest.find('$') {
+            out.push_str(&rest[..start]);
+            let var_ref = &rest[start..];
+
+            if var_ref.starts_with("$${") {
+                // escaped reference is kept literally
+                out.push_str("${");
+                rest = &var_ref[3..];
+            } else if var_ref.starts_with("${") {
+                let end = match var_ref.find('}') {
+                    Some(end) => end,
+                    None => {
+                        return self.fail(
+                            key_path,
+                            format!(
+                                "Unterminated variable reference in `{}`",
+                                key_path
+                            ),
+                            ErrorKind::ConfigVarSyntax,
+                        )
+                    }
+                };
+
+                let value = self.lookup(&var_ref[2..end], key_path)?;
+                out.push_str(&value);
+                rest = &var_ref[end + 1..];
+            } else {
+                out.push('$');
+                rest = &var_ref[1..];
+            }
+        }
+
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use error;
+    use serde::Deserialize;
+    use std::env;
+    use std::fs;
+    use util::test_util::TempDir;
+
+    fn parse(s: &str) -> Value {
+        let v = s.parse::<Value>();
+        assert!(v.is_ok());
+        v.unwrap()
+    }
+
+    #[test]
+    fn test_config_format_from_path() {
+        assert_eq!(ConfigFormat::Toml, ConfigFormat::from_path("app.toml"));
+        assert_eq!(ConfigFormat::Json, ConfigFormat::from_path("app.json"));
+        assert_eq!(ConfigFormat::Yaml, ConfigFormat::from_path("app.yaml"));
+        assert_eq!(ConfigFormat::Yaml, ConfigFormat::from_path("app.YML"));
+        assert_eq!(ConfigFormat::Toml, ConfigFormat::from_path("app.conf"));
+        assert_eq!(ConfigFormat::Toml, ConfigFormat::from_path("app"));
+    }
+
+    #[test]
+    fn test_json_yaml_into_value() {
+        let json: Result<Value, _> = serde_json::from_str(
+            r#"{"pg": {"connection_url": "postgres://localhost", "estimated_cap": 100}}"#,
+        );
+        assert!(json.is_ok());
+
+        let yaml: Result<Value, _> = serde_yaml::from_str(
+            "pg:\n  connection_url: postgres://localhost\n  estimated_cap: 100\n",
+        );
+        assert!(yaml.is_ok());
+
+        let (json, yaml) = (json.unwrap(), yaml.unwrap());
+        assert_eq!(json, yaml);
+        assert_eq!(Some(100), json["pg"]["estimated_cap"].as_integer());
+    }
+
+    #[test]
+    fn test_json_yaml_nulls_into_value() {
+        let json: Result<serde_json::Value, _> = serde_json::from_str(
+            r#"{"general": {"lock_file": "/tmp/app.lock", "jitter": null}}"#,
+        );
+        assert!(json.is_ok());
+
+        let json = Value::deserialize(json_without_nulls(json.unwrap()));
+        assert!(json.is_ok());
+
+        let yaml: Result<serde_yaml::Value, _> = serde_yaml::from_str(
+            "general:\n  lock_file: /tmp/app.lock\n  jitter: ~\n",
+        );
+        assert!(yaml.is_ok());
+
+        let yaml = Value::deserialize(yaml_without_nulls(yaml.unwrap()));
+        assert!(yaml.is_ok());
+
+        let (json, yaml) = (json.unwrap(), yaml.unwrap());
+        assert_eq!(json, yaml);
+        assert!(json["general"].get("jitter").is_none());
+    }
+
+    #[test]
+    fn test_merge_values_deep() {
+        let mut base = parse(
+            r#"
+            [general]
+            lock_file = "/tmp/app.lock"
+            repeat_delay = "5s"
+
+            [pg]
+            connection_url = "postgres://localhost"
+            estimated_cap = 100
+            "#,
+        );
+
+        let overlay = parse(
+            r#"
+            [general]
+            repeat_delay = "1m"
+
+            [pg]
+            estimated_cap = 200
+            "#,
+        );
+
+        merge_values(&mut base, overlay);
+
+        assert_eq!(
+            Some("/tmp/app.lock"),
+            base["general"]["lock_file"].as_str()
+        );
+        assert_eq!(Some("1m"), base["general"]["repeat_delay"].as_str());
+        assert_eq!(
+            Some("postgres://localhost"),
+            base["pg"]["connection_url"].as_str()
+        );
+        assert_eq!(Some(200), base["pg"]["estimated_cap"].as_integer());
+    }
+
+    #[test]
+    fn test_merge_values_replace_non_table() {
+        let mut base = parse("matches = [\"a\", \"b\"]");
+        let overlay = parse("matches = [\"c\"]");
+
+        merge_values(&mut base, overlay);
+
+        let matches = base["matches"].as_array();
+        assert!(matches.is_some());
+        assert_eq!(1, matches.unwrap().len());
+    }
+
+    #[test]
+    fn test_env_var_key_path() {
+        assert_eq!(
+            Some(vec!["pg".to_owned(), "connection_url".to_owned()]),
+            env_var_key_path("MYAPP", "MYAPP__PG__CONNECTION_URL")
+        );
+
+        assert_eq!(None, env_var_key_path("MYAPP", "OTHER__PG"));
+        assert_eq!(None, env_var_key_path("MYAPP", "MYAPPX__PG"));
+        assert_eq!(None, env_var_key_path("MYAPP", "MYAPP__PG____URL"));
+    }
+
+    #[test]
+    fn test_parse_env_value() {
+        assert_eq!(Value::Integer(42), parse_env_value("42"));
+        assert_eq!(Value::Boolean(true), parse_env_value("true"));
+        assert_eq!(
+            Value::String("postgres://localhost".to_owned()),
+            parse_env_value("postgres://localhost")
+        );
+        assert_eq!(
+            Value::String("quoted".to_owned()),
+            parse_env_value("\"quoted\"")
+        );
+    }
+
+    #[test]
+    fn test_apply_env_overrides() {
+        let mut root = parse(
+            r#"
+            [pg]
+            connection_url = "postgres://localhost"
+            estimated_cap = 100
+            "#,
+        );
+
+        let vars = vec![
+            (
+                "MYAPP__PG__CONNECTION_URL".to_owned(),
+                "postgres://remote".to_owned(),
+            ),
+            ("MYAPP__PG__ESTIMATED_CAP".to_owned(), "300".to_owned()),
+            ("MYAPP__FLUENTD__TAG".to_owned(), "app.tag".to_owned()),
+            ("UNRELATED".to_owned(), "value".to_owned()),
+        ];
+
+        let mut sources = ConfigSources::new();
+        let res = apply_env_overrides(&mut root, "MYAPP", vars, &mut sources);
+        assert!(res.is_ok());
+
+        assert_eq!(
+            Some("postgres://remote"),
+            root["pg"]["connection_url"].as_str()
+        );
+        assert_eq!(Some(300), root["pg"]["estimated_cap"].as_integer());
+        assert_eq!(Some("app.tag"), root["fluentd"]["tag"].as_str());
+        assert!(root.get("unrelated").is_none());
+
+        assert_eq!(
+            Some(&ConfigSource::Env("MYAPP__PG__ESTIMATED_CAP".to_owned())),
+            sources.source_of("pg.estimated_cap")
+        );
+    }
+
+    #[test]
+    fn test_apply_env_overrides_non_table() {
+        let mut root = parse("lock_file = \"/tmp/app.lock\"");
+
+        let vars = vec![("MYAPP__LOCK_FILE__INNER".to_owned(), "x".to_owned())];
+
+        let res = apply_env_overrides(
+            &mut root,
+            "MYAPP",
+            vars,
+            &mut ConfigSources::new(),
+        );
+        assert!(res.is_err());
+
+        let e = res.unwrap_err();
+        assert_eq!("MYAPP__LOCK_FILE__INNER", e.target);
+        assert_eq!("Key `lock_file` is not a table", e.inner.msg);
+    }
+
+    #[test]
+    fn test_set_key_path_conflict() {
+        let mut root = parse(
+            "port = 1
+[pg]
+port = 2",
+        );
+
+        let keys = |path: &str| -> Vec<String> {
+            path.split('.').map(|k| k.to_owned()).collect()
+        };
+
+        let res = set_key_path(&mut root, &keys("port.inner"), parse(""));
+        assert!(res.is_err());
+        assert_eq!("Key `port` is not a table", res.unwrap_err().msg);
+
+        let res = set_key_path(&mut root, &keys("pg.port.inner"), parse(""));
+        assert!(res.is_err());
+        assert_eq!("Key `pg.port` is not a table", res.unwrap_err().msg);
+
+        let res = set_key_path(&mut root, &keys("pg.host"), parse(""));
+        assert!(res.is_ok());
+        assert!(root["pg"]["host"].is_table());
+    }
+
+    #[test]
+    fn test_config_sources() {
+        let base = ConfigSource::File(PathBuf::from("app.toml"));
+        let overlay = ConfigSource::File(PathBuf::from("app.prod.toml"));
+
+        let mut sources = ConfigSources::new();
+        sources.record(
+            "",
+            &parse(
+                "[pg]
+port = 1
+host = \"a\"",
+            ),
+            &base,
+        );
+        sources.record(
+            "",
+            &parse(
+                "[pg]
+port = 2",
+            ),
+            &overlay,
+        );
+
+        assert_eq!(Some(&overlay), sources.source_of("pg.port"));
+        assert_eq!(Some(&base), sources.source_of("pg.host"));
+        assert_eq!(None, sources.source_of("pg"));
+
+        // a scalar replacing the table takes over the keys below it
+        sources.record("pg", &Value::Integer(1), &overlay);
+        assert_eq!(Some(&overlay), sources.source_of("pg.host.inner"));
+    }
+
+    #[test]
+    fn test_resolve_secrets() {
+        let dir = TempDir::new("resolve_secrets");
+        let secret_path = dir.join("pg_url");
+
+        assert!(fs::write(&secret_path, "file-secret\n").is_ok());
+        env::set_var("MEGA_COLL_TEST_RESOLVE_SECRETS", "env-secret");
+
+        let mut root = parse(&format!(
+            r#"
+            plain = "env-like but not a ref"
+            ports = [1, 2]
+            uri = "file:///var/data"
+
+            [krb5.auth]
+            type = "Password"
+            value = "env:MEGA_COLL_TEST_RESOLVE_SECRETS"
+
+            [pg]
+            connection_url = "file:{}"
+            "#,
+            secret_path.display()
+        ));
+
+        let res: error::Result<()> = resolve_secrets(
+            &mut root,
+            &[
+                "krb5.auth.value",
+                "pg.connection_url",
+                "pg.missing",
+                "ports",
+            ],
+        );
+
+        assert!(res.is_ok());
+
+        assert_eq!(Some("env-secret"), root["krb5"]["auth"]["value"].as_str());
+        assert_eq!(Some("file-secret"), root["pg"]["connection_url"].as_str());
+        assert_eq!(Some("env-like but not a ref"), root["plain"].as_str());
+        assert_eq!(Some("file:///var/data"), root["uri"].as_str());
+    }
+
+    #[test]
+    fn test_resolve_secrets_missing() {
+        let mut env_root =
+            parse("[pg]\nconnection_url = \"env:MEGA_COLL_TEST_NOT_SET\"");
+
+        let res: error::Result<()> =
+            resolve_secrets(&mut env_root, &["pg.connection_url"]);
+        assert!(res.is_err());
+        assert_eq!(
+            ErrorKind::SecretEnvVarMissing,
+            *res.unwrap_err().inner.get_context()
+        );
+
+        let mut file_root =
+            parse("[pg]\nconnection_url = \"file:/mega_coll/not/exist\"");
+
+        let res: error::Result<()> =
+            resolve_secrets(&mut file_root, &["pg.connection_url"]);
+        assert!(res.is_err());
+        assert_eq!(
+            ErrorKind::SecretFileRead,
+            *res.unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_take_includes() {
+        let mut single = parse("include = \"common.toml\"");
+        let res = take_includes(&mut single);
+        assert!(res.is_ok());
+        assert_eq!(vec!["common.toml".to_owned()], res.unwrap());
+        assert!(single.get("include").is_none());
+
+        let mut multi = parse("include = [\"a.toml\", \"b.toml\"]");
+        let res = take_includes(&mut multi);
+        assert!(res.is_ok());
+        assert_eq!(2, res.unwrap().len());
+
+        let mut invalid = parse("include = 1");
+        assert!(take_includes(&mut invalid).is_err());
+    }
+
+    #[test]
+    fn test_interpolate_vars() {
+        env::set_var("MEGA_COLL_TEST_INTERPOLATE_HOST", "env-host");
+
+        let mut root = parse(
+            r#"
+            [vars]
+            root = "/data"
+            user_dir = "${root}/users"
+
+            [hdfs]
+            path = "${user_dir}/daily"
+            copy_to = "${MEGA_COLL_TEST_INTERPOLATE_HOST}:${root}"
+            "#,
+        );
+
+        let res: error::Result<()> =
+            interpolate_vars(&mut root, "app.toml", &ConfigSources::new());
+        assert!(res.is_ok());
+
+        assert!(root.get("vars").is_none());
+        assert_eq!(Some("/data/users/daily"), root["hdfs"]["path"].as_str());
+        assert_eq!(Some("env-host:/data"), root["hdfs"]["copy_to"].as_str());
+    }
+
+    #[test]
+    fn test_interpolate_vars_errors() {
+        let check = |s: &str, kind: ErrorKind| {
+            let mut root = parse(s);
+            let res: error::Result<()> =
+                interpolate_vars(&mut root, "app.toml", &ConfigSources::new());
+            assert!(res.is_err());
+            assert_eq!(kind, *res.unwrap_err().inner.get_context());
+        };
+
+        check(
+            "path = \"${MEGA_COLL_TEST_UNDEFINED}\"",
+            ErrorKind::ConfigVarUndefined,
+        );
+
+        check(
+            "path = \"${a}\"\n[vars]\na = \"${b}\"\nb = \"${a}\"",
+            ErrorKind::ConfigVarCycle,
+        );
+
+        check("path = \"${unterminated\"", ErrorKind::ConfigVarSyntax);
+    }
+}
@@ -0,0 +1,148 @@
+use error::custom::PathError;
+use error::{Error, ErrorKind};
+use failure::{Fail, ResultExt};
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+const READ_BUF_LEN: usize = 64 * 1024;
+
+lazy_static! {
+    static ref CRC32_TABLE: [u32; 256] = crc_table(0xEDB8_8320);
+    static ref CRC32C_TABLE: [u32; 256] = crc_table(0x82F6_3B78);
+}
+
+// the two checksum types that HDFS stores per chunk
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CrcType {
+    Crc32,
+    Crc32c,
+}
+
+impl CrcType {
+    fn table(self) -> &'static [u32; 256] {
+        match self {
+            CrcType::Crc32 => &CRC32_TABLE,
+            CrcType::Crc32c => &CRC32C_TABLE,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Crc {
+    table: &'static [u32; 256],
+    crc: u32,
+}
+
+impl Crc {
+    pub fn new(crc_type: CrcType) -> Crc {
+        Crc {
+            table: crc_type.table(),
+            crc: !0,
+        }
+    }
+
+    pub fn update(&mut self, buf: &[u8]) {
+        for b in buf {
+            let i = ((self.crc ^ u32::from(*b)) & 0xff) as usize;
+            self.crc = self.table[i] ^ (self.crc >> 8);
+        }
+    }
+
+    pub fn value(&self) -> u32 {
+        !self.crc
+    }
+}
+
+// reflected table for the given reversed polynomial
+fn crc_table(poly: u32) -> [u32; 256] {
+    let mut table = [0u32; 256];
+
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut crc = i as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ poly
+            } else {
+                crc >> 1
+            };
+        }
+
+        *entry = crc;
+    }
+
+    table
+}
+
+pub fn file_crc<P, K>(crc_type: CrcType, p: P) -> Result<u32, Error<K>>
+where
+    P: AsRef<Path>,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let p = p.as_ref();
+
+    let mut file = File::open(p)
+        .map_err(|e| PathError::new(p, e))
+        .context(ErrorKind::FileIo)?;
+
+    let mut crc = Crc::new(crc_type);
+    let mut buf = vec![0; READ_BUF_LEN];
+
+    loop {
+        let len = file
+            .read(&mut buf)
+            .map_err(|e| PathError::new(p, e))
+            .context(ErrorKind::FileIo)?;
+
+        if len == 0 {
+            break;
+        }
+
+        crc.update(&buf[..len]);
+    }
+
+    Ok(crc.value())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use error;
+    use std::fs;
+    use util::test_util::TempDir;
+
+    #[test]
+    fn test_crc_check_values() {
+        let mut crc = Crc::new(CrcType::Crc32);
+        crc.update(b"123456789");
+        assert_eq!(0xCBF4_3926, crc.value());
+
+        let mut crc = Crc::new(CrcType::Crc32c);
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(0xE306_9283, crc.value());
+
+        assert_eq!(0, Crc::new(CrcType::Crc32c).value());
+    }
+
+    #[test]
+    fn test_file_crc() {
+        let dir = TempDir::new("file_crc");
+        let file = dir.join("data.bin");
+
+        assert!(fs::write(&file, b"123456789").is_ok());
+
+        let crc: error::Result<u32> = file_crc(CrcType::Crc32c, &file);
+        assert!(crc.is_ok());
+
+        let crc = crc.unwrap();
+        assert_eq!(0xE306_9283, crc);
+
+        let missing = file.with_extension("missing");
+        let crc: error::Result<u32> = file_crc(CrcType::Crc32c, &missing);
+        assert_eq!(ErrorKind::FileIo, *crc.unwrap_err().inner.get_context());
+    }
+}
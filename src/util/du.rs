@@ -0,0 +1,294 @@
+use error::custom::PathError;
+use error::{Error, ErrorKind};
+use failure::{Fail, ResultExt};
+use json::{DirUsage, DirUsageBuilder};
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// st_blocks is always counted in 512-byte units regardless of block size
+const ST_BLOCK_SIZE: u64 = 512;
+
+#[derive(Debug)]
+pub struct DirUsageOptions {
+    pub max_depth: usize,
+    pub parallel: bool,
+    pub one_file_system: bool,
+}
+
+impl Default for DirUsageOptions {
+    fn default() -> DirUsageOptions {
+        DirUsageOptions {
+            max_depth: 1,
+            parallel: false,
+            one_file_system: true,
+        }
+    }
+}
+
+impl DirUsageOptions {
+    pub fn max_depth(mut self, max_depth: usize) -> DirUsageOptions {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn parallel(mut self, parallel: bool) -> DirUsageOptions {
+        self.parallel = parallel;
+        self
+    }
+
+    pub fn one_file_system(mut self, one_file_system: bool) -> DirUsageOptions {
+        self.one_file_system = one_file_system;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct DirUsageReport {
+    pub entries: Vec<DirUsage>,
+    pub skipped: Vec<PathError<io::Error>>,
+}
+
+impl DirUsageReport {
+    pub fn skipped_count(&self) -> usize {
+        self.skipped.len()
+    }
+}
+
+#[derive(Default, Debug)]
+struct DirWalk {
+    apparent_size: u64,
+    allocated_size: u64,
+    file_count: u64,
+    dir_count: u64,
+    entries: Vec<DirUsage>,
+    skipped: Vec<PathError<io::Error>>,
+}
+
+impl DirWalk {
+    fn merge(&mut self, other: DirWalk) {
+        self.apparent_size += other.apparent_size;
+        self.allocated_size += other.allocated_size;
+        self.file_count += other.file_count;
+        self.dir_count += other.dir_count;
+        self.entries.extend(other.entries);
+        self.skipped.extend(other.skipped);
+    }
+
+    fn add_metadata(&mut self, meta: &fs::Metadata) {
+        self.apparent_size += meta.len();
+        self.allocated_size += meta.blocks() * ST_BLOCK_SIZE;
+    }
+}
+
+pub fn dir_usage<P, K>(
+    root: P,
+    opts: &DirUsageOptions,
+) -> Result<DirUsageReport, Error<K>>
+where
+    P: AsRef<Path>,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let root = root.as_ref();
+
+    let meta = fs::symlink_metadata(root)
+        .map_err(|e| PathError::new(root, e))
+        .context(ErrorKind::DirWalk)?;
+
+    if !meta.is_dir() {
+        Err(PathError::new(
+            root,
+            io::Error::new(io::ErrorKind::InvalidInput, "Not a directory"),
+        ))
+        .context(ErrorKind::DirWalk)?;
+    }
+
+    let seen_inodes = Mutex::new(HashSet::new());
+    let mut walk = walk_dir(root, &meta, 0, meta.dev(), &seen_inodes, opts);
+    walk.entries.sort_by(|a, b| a.path().cmp(b.path()));
+
+    for e in &walk.skipped {
+        warn!("Skipped unreadable entry: {}", e);
+    }
+
+    Ok(DirUsageReport {
+        entries: walk.entries,
+        skipped: walk.skipped,
+    })
+}
+
+fn walk_dir(
+    dir: &Path,
+    meta: &fs::Metadata,
+    depth: usize,
+    root_dev: u64,
+    seen_inodes: &Mutex<HashSet<(u64, u64)>>,
+    opts: &DirUsageOptions,
+) -> DirWalk {
+    let mut walk = DirWalk::default();
+    walk.dir_count += 1;
+    walk.add_metadata(meta);
+
+    let mut sub_dirs: Vec<(PathBuf, fs::Metadata)> = vec![];
+
+    match fs::read_dir(dir) {
+        Ok(read_dir) => {
+            for entry in read_dir {
+                let entry_path = match entry {
+                    Ok(entry) => entry.path(),
+                    Err(e) => {
+                        walk.skipped.push(PathError::new(dir, e));
+                        continue;
+                    }
+                };
+
+                // symlinks are counted as is and never followed
+                let entry_meta = match fs::symlink_metadata(&entry_path) {
+                    Ok(entry_meta) => entry_meta,
+                    Err(e) => {
+                        walk.skipped.push(PathError::new(entry_path, e));
+                        continue;
+                    }
+                };
+
+                if entry_meta.is_dir() {
+                    if !opts.one_file_system || entry_meta.dev() == root_dev {
+                        sub_dirs.push((entry_path, entry_meta));
+                    }
+                } else if entry_meta.nlink() <= 1
+                    || first_link(seen_inodes, &entry_meta)
+                {
+                    walk.file_count += 1;
+                    walk.add_metadata(&entry_meta);
+                }
+            }
+        }
+        Err(e) => walk.skipped.push(PathError::new(dir, e)),
+    }
+
+    let walk_sub_dir = |(path, meta): &(PathBuf, fs::Metadata)| {
+        walk_dir(path, meta, depth + 1, root_dev, seen_inodes, opts)
+    };
+
+    let sub_walks: Vec<DirWalk> = if opts.parallel {
+        sub_dirs.par_iter().map(walk_sub_dir).collect()
+    } else {
+        sub_dirs.iter().map(walk_sub_dir).collect()
+    };
+
+    for sub_walk in sub_walks {
+        walk.merge(sub_walk);
+    }
+
+    if depth <= opts.max_depth {
+        walk.entries.push(
+            DirUsageBuilder::default()
+                .path(dir.to_string_lossy())
+                .depth(depth)
+                .apparent_size(walk.apparent_size)
+                .allocated_size(walk.allocated_size)
+                .file_count(walk.file_count)
+                .dir_count(walk.dir_count)
+                .build(),
+        );
+    }
+
+    walk
+}
+
+// like du, a hard linked file only counts under the first link walked
+fn first_link(
+    seen_inodes: &Mutex<HashSet<(u64, u64)>>,
+    meta: &fs::Metadata,
+) -> bool {
+    let mut seen_inodes = match seen_inodes.lock() {
+        Ok(seen_inodes) => seen_inodes,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    seen_inodes.insert((meta.dev(), meta.ino()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use error;
+    use util::test_util::TempDir;
+
+    #[test]
+    fn test_dir_usage() {
+        let root = TempDir::new("dir_usage");
+
+        assert!(fs::create_dir_all(root.join("a/deep")).is_ok());
+        assert!(fs::create_dir_all(root.join("b")).is_ok());
+        assert!(fs::write(root.join("top.txt"), "1234").is_ok());
+        assert!(fs::write(root.join("a/one.txt"), "12").is_ok());
+        assert!(fs::write(root.join("a/deep/two.txt"), "123").is_ok());
+
+        for parallel in &[false, true] {
+            let report: error::Result<DirUsageReport> = dir_usage(
+                &root,
+                &DirUsageOptions::default().max_depth(1).parallel(*parallel),
+            );
+
+            assert!(report.is_ok());
+            let report = report.unwrap();
+            assert_eq!(0, report.skipped_count());
+
+            // root, a and b only, a/deep is beyond max depth
+            assert_eq!(3, report.entries.len());
+
+            let root_usage = &report.entries[0];
+            assert_eq!(root.to_string_lossy(), root_usage.path());
+            assert_eq!(3, *root_usage.file_count());
+            assert_eq!(4, *root_usage.dir_count());
+
+            let a_usage = &report.entries[1];
+            assert_eq!(1, *a_usage.depth());
+            assert_eq!(2, *a_usage.file_count());
+            assert_eq!(2, *a_usage.dir_count());
+        }
+    }
+
+    #[test]
+    fn test_dir_usage_hard_links() {
+        let root = TempDir::new("dir_usage_links");
+
+        assert!(fs::create_dir_all(root.join("a")).is_ok());
+        assert!(fs::create_dir_all(root.join("b")).is_ok());
+        assert!(fs::write(root.join("a/one.txt"), "1234").is_ok());
+        assert!(
+            fs::hard_link(root.join("a/one.txt"), root.join("b/one.txt"))
+                .is_ok()
+        );
+
+        let report: error::Result<DirUsageReport> =
+            dir_usage(&root, &DirUsageOptions::default().parallel(true));
+
+        assert!(report.is_ok());
+
+        let report = report.unwrap();
+        let root_usage = &report.entries[0];
+        assert_eq!(1, *root_usage.file_count());
+
+        // the file counts under exactly one of a and b
+        let sub_dir_files =
+            *report.entries[1].file_count() + *report.entries[2].file_count();
+
+        assert_eq!(1, sub_dir_files);
+    }
+
+    #[test]
+    fn test_dir_usage_not_dir() {
+        let report: error::Result<DirUsageReport> =
+            dir_usage("/mega_coll/not/exist", &DirUsageOptions::default());
+
+        assert!(report.is_err());
+    }
+}
@@ -1,32 +1,386 @@
-use error::custom::PathError;
+use chrono::{DateTime, Local};
+use conf::fs::MountsConfig;
+use error::custom::{LockHeldError, MsgError, PathError};
 use error::{Error, ErrorKind};
 use failure::{Fail, ResultExt};
 use fs2::FileExt;
+use json::{Storage, StorageBuilder};
+use libc;
+use serde::ser::Serialize;
+use serde_json;
+use std::cmp;
+use std::collections::HashSet;
+use std::ffi::CString;
 use std::fmt::Debug;
-use std::fs::{File, OpenOptions};
-use std::io::Read;
-use std::path::Path;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::ops::Deref;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use toml;
+use util::process::{hostname, pid_alive, process_started};
 
-pub fn lock_file<P: AsRef<Path>, K>(file_path: P) -> Result<File, Error<K>>
+const LOCK_POLL_INTERVAL_MS: u64 = 100;
+const PROC_MOUNTS_PATH: &str = "/proc/self/mounts";
+
+const PSEUDO_FS_TYPES: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "overlay",
+    "proc",
+    "pstore",
+    "securityfs",
+    "squashfs",
+    "sysfs",
+    "tmpfs",
+    "tracefs",
+];
+
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LockInfo {
+    pub pid: u32,
+    pub hostname: String,
+    pub started: DateTime<Local>,
+}
+
+impl LockInfo {
+    pub fn current() -> LockInfo {
+        LockInfo {
+            pid: process::id(),
+            hostname: hostname(),
+            started: Local::now(),
+        }
+    }
+
+    pub fn is_local(&self) -> bool {
+        self.hostname == hostname()
+    }
+
+    pub fn is_alive(&self) -> bool {
+        if !self.is_local() || !pid_alive(self.pid) {
+            return false;
+        }
+
+        // a process at the pid that started after the lock was taken means
+        // the holder died and its pid was reused, /proc/<pid>/stat start
+        // times only have a resolution of one second
+        match process_started(self.pid) {
+            Some(pid_started) => {
+                pid_started.timestamp() <= self.started.timestamp() + 1
+            }
+            None => true,
+        }
+    }
+
+    pub fn is_stale(&self) -> bool {
+        // liveness can only be checked for processes on the same host
+        self.is_local() && !self.is_alive()
+    }
+
+    fn to_held_error(&self) -> LockHeldError {
+        LockHeldError::new(
+            self.pid,
+            self.hostname.as_str(),
+            self.started,
+            self.is_stale(),
+        )
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct LockOptions {
+    pub break_stale: bool,
+    pub shared: bool,
+    pub remove_on_drop: bool,
+    pub timeout: Option<Duration>,
+}
+
+impl LockOptions {
+    // also takes over records from other hosts, whose liveness is unknown
+    pub fn break_stale(mut self, break_stale: bool) -> LockOptions {
+        self.break_stale = break_stale;
+        self
+    }
+
+    pub fn shared(mut self, shared: bool) -> LockOptions {
+        self.shared = shared;
+        self
+    }
+
+    pub fn remove_on_drop(mut self, remove_on_drop: bool) -> LockOptions {
+        self.remove_on_drop = remove_on_drop;
+        self
+    }
+
+    pub fn timeout<T>(mut self, timeout: T) -> LockOptions
+    where
+        T: Into<Option<Duration>>,
+    {
+        self.timeout = timeout.into();
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct LockGuard {
+    file: File,
+    path: PathBuf,
+    shared: bool,
+    remove_on_drop: bool,
+}
+
+impl LockGuard {
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+}
+
+impl Deref for LockGuard {
+    type Target = File;
+
+    fn deref(&self) -> &File {
+        &self.file
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        // shared holders never own the holder info in the file
+        if !self.shared {
+            // removed while still locked, so whoever locks the unlinked file
+            // next sees it is gone from the path and retries
+            if self.remove_on_drop {
+                let _ = fs::remove_file(&self.path);
+            } else {
+                let _ = self.file.set_len(0);
+            }
+        }
+
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+pub fn lock_file<P, K>(file_path: P) -> Result<LockGuard, Error<K>>
+where
+    P: AsRef<Path>,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    lock_file_with(file_path, &LockOptions::default())
+}
+
+pub fn lock_file_shared<P, K>(file_path: P) -> Result<LockGuard, Error<K>>
+where
+    P: AsRef<Path>,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    lock_file_with(file_path, &LockOptions::default().shared(true))
+}
+
+pub fn lock_file_wait<P, K>(
+    file_path: P,
+    timeout: Duration,
+) -> Result<LockGuard, Error<K>>
+where
+    P: AsRef<Path>,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    lock_file_with(file_path, &LockOptions::default().timeout(timeout))
+}
+
+pub fn lock_file_with<P, K>(
+    file_path: P,
+    opts: &LockOptions,
+) -> Result<LockGuard, Error<K>>
 where
     P: AsRef<Path>,
     K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
 {
     let file_path = file_path.as_ref();
+    let start = Instant::now();
+
+    let lock_kind = if opts.shared {
+        ErrorKind::LockFileSharedLock
+    } else {
+        ErrorKind::LockFileExclusiveLock
+    };
+
+    loop {
+        let holder = match try_lock(file_path, opts)? {
+            Ok(file) => {
+                return Ok(LockGuard {
+                    file,
+                    path: file_path.to_owned(),
+                    shared: opts.shared,
+                    remove_on_drop: opts.remove_on_drop,
+                })
+            }
+            Err(holder) => holder,
+        };
+
+        let elapsed = start.elapsed();
+
+        let kind = match opts.timeout {
+            Some(timeout) if elapsed < timeout => {
+                let poll_interval =
+                    Duration::from_millis(LOCK_POLL_INTERVAL_MS);
+                thread::sleep(cmp::min(poll_interval, timeout - elapsed));
+                continue;
+            }
+            Some(_) => ErrorKind::LockFileWaitTimeout,
+            None => lock_kind,
+        };
+
+        match holder {
+            Some(holder) => {
+                Err(PathError::new(file_path, holder.to_held_error()))
+                    .context(kind)?
+            }
+            None => Err(PathError::new(
+                file_path,
+                MsgError::new("Lock is held by an unknown holder"),
+            ))
+            .context(kind)?,
+        }
+    }
+}
+
+fn try_lock<K>(
+    file_path: &Path,
+    opts: &LockOptions,
+) -> Result<Result<File, Option<LockInfo>>, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let (flock, holder) = loop {
+        // opening must not wipe the info of a current holder
+        let flock = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(file_path)
+            .map_err(|e| PathError::new(file_path, e))
+            .context(ErrorKind::LockFileOpen)?;
 
-    let flock = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .open(file_path)
-        .map_err(|e| PathError::new(file_path, e))
-        .context(ErrorKind::LockFileOpen)?;
+        let holder = read_lock_info(&flock);
+
+        let lock_res = if opts.shared {
+            FileExt::try_lock_shared(&flock)
+        } else {
+            flock.try_lock_exclusive()
+        };
+
+        if lock_res.is_err() {
+            return Ok(Err(holder));
+        }
+
+        // a holder with remove_on_drop may have unlinked the file after it
+        // was opened here, leaving the lock on an inode nobody else can see
+        let unchanged = is_locked_path(&flock, file_path)
+            .map_err(|e| PathError::new(file_path, e))
+            .context(ErrorKind::LockFileOpen)?;
+
+        if unchanged {
+            break (flock, holder);
+        }
+
+        let _ = FileExt::unlock(&flock);
+    };
+
+    // flock is unreliable on network file systems, so a holder recorded in
+    // the file still counts as contention unless it is verifiably gone and
+    // the caller asked to break it, which cannot be known for other hosts
+    let mut write_kind = ErrorKind::LockFileWrite;
+
+    if let Some(holder) = holder {
+        if !holder.is_local() || holder.pid != process::id() {
+            if holder.is_alive() || !opts.break_stale {
+                let _ = FileExt::unlock(&flock);
+                return Ok(Err(Some(holder)));
+            }
+
+            warn!(
+                "Breaking stale lock {:?} held by pid {} on {} since {}",
+                file_path, holder.pid, holder.hostname, holder.started
+            );
+
+            // the flock is held, so the record is replaced in place rather
+            // than by swapping in a new file, which two breakers could both
+            // do at once
+            write_kind = ErrorKind::LockFileStaleBreak;
+
+            if opts.shared {
+                flock
+                    .set_len(0)
+                    .map_err(|e| PathError::new(file_path, e))
+                    .context(write_kind)?;
+            }
+        }
+    }
+
+    if !opts.shared {
+        write_lock_info(&flock, &LockInfo::current())
+            .map_err(|e| PathError::new(file_path, e))
+            .context(write_kind)?;
+    }
+
+    Ok(Ok(flock))
+}
+
+fn is_locked_path(flock: &File, file_path: &Path) -> io::Result<bool> {
+    let locked = flock.metadata()?;
+
+    match fs::metadata(file_path) {
+        Ok(current) => {
+            Ok(locked.dev() == current.dev() && locked.ino() == current.ino())
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn read_lock_info(mut flock: &File) -> Option<LockInfo> {
+    let mut buf = String::new();
 
     flock
-        .try_lock_exclusive()
-        .map_err(|e| PathError::new(file_path, e))
-        .context(ErrorKind::LockFileExclusiveLock)?;
+        .seek(SeekFrom::Start(0))
+        .and_then(|_| flock.read_to_string(&mut buf))
+        .ok()
+        .and_then(|_| serde_json::from_str(&buf).ok())
+}
 
-    Ok(flock)
+fn write_lock_info(mut flock: &File, info: &LockInfo) -> io::Result<()> {
+    flock.set_len(0)?;
+    flock.seek(SeekFrom::Start(0))?;
+    serde_json::to_writer(&mut flock, info)?;
+    flock.sync_data()
 }
 
 pub fn read_from_file<P, K>(p: P) -> Result<String, Error<K>>
@@ -44,3 +398,620 @@ where
     file.read_to_string(&mut buf).context(ErrorKind::FileIo)?;
     Ok(buf)
 }
+
+pub fn write_to_file_atomic<P, C, K>(p: P, contents: C) -> Result<(), Error<K>>
+where
+    P: AsRef<Path>,
+    C: AsRef<[u8]>,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let p = p.as_ref();
+
+    let file_name = p
+        .file_name()
+        .ok_or_else(|| PathError::new(p, MsgError::new("Missing file name")))
+        .context(ErrorKind::AtomicFileWrite)?;
+
+    let dir = match p.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    // the temporary file must live in the same directory for the rename to
+    // stay atomic
+    let tmp_path = dir.join(format!(
+        ".{}.tmp.{}.{}",
+        file_name.to_string_lossy(),
+        process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+
+    let write_res = write_and_sync(&tmp_path, contents.as_ref())
+        .map_err(|e| PathError::new(&tmp_path, e))
+        .context(ErrorKind::AtomicFileWrite);
+
+    if let Err(e) = write_res {
+        let _ = fs::remove_file(&tmp_path);
+        Err(e)?;
+    }
+
+    let rename_res = fs::rename(&tmp_path, p)
+        .map_err(|e| PathError::new(p, e))
+        .context(ErrorKind::AtomicFileRename);
+
+    if let Err(e) = rename_res {
+        let _ = fs::remove_file(&tmp_path);
+        Err(e)?;
+    }
+
+    File::open(dir)
+        .and_then(|dir_file| dir_file.sync_all())
+        .map_err(|e| PathError::new(dir, e))
+        .context(ErrorKind::AtomicFileRename)?;
+
+    Ok(())
+}
+
+fn write_and_sync(p: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = File::create(p)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+pub fn write_json_to_file_atomic<P, T, K>(
+    p: P,
+    value: &T,
+) -> Result<(), Error<K>>
+where
+    P: AsRef<Path>,
+    T: Serialize,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let p = p.as_ref();
+
+    let contents = serde_json::to_vec_pretty(value)
+        .map_err(|e| PathError::new(p, e))
+        .context(ErrorKind::ToStringPretty)?;
+
+    write_to_file_atomic(p, contents)
+}
+
+pub fn write_toml_to_file_atomic<P, T, K>(
+    p: P,
+    value: &T,
+) -> Result<(), Error<K>>
+where
+    P: AsRef<Path>,
+    T: Serialize,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let p = p.as_ref();
+
+    let contents = toml::to_string_pretty(value)
+        .map_err(|e| PathError::new(p, e))
+        .context(ErrorKind::ToStringPretty)?;
+
+    write_to_file_atomic(p, contents)
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MountPoint {
+    pub device: String,
+    pub mount_path: String,
+    pub fs_type: String,
+    pub options: String,
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(cast_lossless))]
+pub fn local_storage<P, K>(p: P) -> Result<Storage, Error<K>>
+where
+    P: AsRef<Path>,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let p = p.as_ref();
+
+    let c_path = CString::new(p.as_os_str().as_bytes())
+        .map_err(|e| PathError::new(p, e))
+        .context(ErrorKind::Statvfs)?;
+
+    let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+    let ret = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+
+    if ret != 0 {
+        Err(PathError::new(p, io::Error::last_os_error()))
+            .context(ErrorKind::Statvfs)?;
+    }
+
+    // without blocks the used and remaining proportions would be NaN
+    if stat.f_blocks == 0 {
+        Err(PathError::new(
+            p,
+            MsgError::new("Zero f_blocks from statvfs"),
+        ))
+        .context(ErrorKind::StatvfsNoBlocks)?;
+    }
+
+    // the statvfs fields are only 32 bits wide on some 32-bit targets
+    let frsize = stat.f_frsize as u64;
+    let capacity = stat.f_blocks as u64 * frsize;
+    let free = stat.f_bfree as u64 * frsize;
+
+    Ok(StorageBuilder::default()
+        .path(p.to_string_lossy())
+        .capacity(capacity)
+        .used(capacity - free)
+        .available(stat.f_bavail as u64 * frsize)
+        .build())
+}
+
+fn unescape_mount_field(field: &str) -> String {
+    // /proc/self/mounts encodes space, tab, newline and backslash as octal
+    field
+        .replace("\\040", " ")
+        .replace("\\011", "\t")
+        .replace("\\012", "\n")
+        .replace("\\134", "\\")
+}
+
+pub fn parse_mounts(content: &str) -> Vec<MountPoint> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            if fields.len() < 4 {
+                return None;
+            }
+
+            Some(MountPoint {
+                device: unescape_mount_field(fields[0]),
+                mount_path: unescape_mount_field(fields[1]),
+                fs_type: fields[2].to_owned(),
+                options: fields[3].to_owned(),
+            })
+        })
+        .collect()
+}
+
+pub fn mount_points<K>(conf: &MountsConfig) -> Result<Vec<MountPoint>, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let mounts = parse_mounts(&read_from_file(PROC_MOUNTS_PATH)?);
+    Ok(dedup_mount_points(filter_mount_points(mounts, conf)))
+}
+
+pub fn filter_mount_points(
+    mounts: Vec<MountPoint>,
+    conf: &MountsConfig,
+) -> Vec<MountPoint> {
+    mounts
+        .into_iter()
+        .filter(|m| {
+            if conf.include_fs_types.is_empty() {
+                !PSEUDO_FS_TYPES.contains(&m.fs_type.as_str())
+            } else {
+                conf.include_fs_types.contains(&m.fs_type)
+            }
+        })
+        .filter(|m| !conf.exclude_fs_types.contains(&m.fs_type))
+        .collect()
+}
+
+// the same file system may be mounted at several places, e.g. bind mounts,
+// which is told by st_dev since virtual file systems all share device names
+// such as "tmpfs"
+pub fn dedup_mount_points(mounts: Vec<MountPoint>) -> Vec<MountPoint> {
+    let mut seen_devs = HashSet::new();
+
+    mounts
+        .into_iter()
+        .filter(|m| match fs::metadata(&m.mount_path) {
+            Ok(meta) => seen_devs.insert(meta.dev()),
+            // kept for local_storage to report the error
+            Err(_) => true,
+        })
+        .collect()
+}
+
+pub fn local_storages<K>(conf: &MountsConfig) -> Result<Vec<Storage>, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let no_blocks = K::from(ErrorKind::StatvfsNoBlocks);
+    let mut storages = vec![];
+
+    for m in mount_points(conf)? {
+        match local_storage(&m.mount_path) {
+            Ok(storage) => storages.push(storage),
+            // e.g. FUSE file systems that do not report their size
+            Err(ref e) if *e.inner.get_context() == no_blocks => {
+                debug!("Skipping {} without blocks", m.mount_path)
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(storages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use error;
+    use std::process::Command;
+    use util::test_util::TempDir;
+
+    fn dead_pid() -> u32 {
+        let child = Command::new("true").spawn();
+        assert!(child.is_ok());
+
+        let mut child = child.unwrap();
+        let pid = child.id();
+        assert!(child.wait().is_ok());
+        pid
+    }
+
+    #[test]
+    fn test_lock_file_contention_reports_holder() {
+        let dir = TempDir::new("lock_contention");
+        let lock_path = dir.join("app.lock");
+
+        let first: error::Result<LockGuard> = lock_file(&lock_path);
+        assert!(first.is_ok());
+
+        let second: error::Result<LockGuard> = lock_file(&lock_path);
+        assert!(second.is_err());
+
+        let e = second.unwrap_err();
+        assert_eq!(ErrorKind::LockFileExclusiveLock, *e.inner.get_context());
+        assert!(e.to_string().contains(&format!("pid: {}", process::id())));
+    }
+
+    fn write_holder(lock_path: &Path, holder: &LockInfo) {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(lock_path);
+
+        assert!(file.is_ok());
+        assert!(write_lock_info(&file.unwrap(), holder).is_ok());
+    }
+
+    #[test]
+    fn test_lock_file_break_stale() {
+        let dir = TempDir::new("lock_break_stale");
+        let lock_path = dir.join("app.lock");
+
+        // a holder killed without releasing leaves its info behind
+        let stale_info = LockInfo {
+            pid: dead_pid(),
+            hostname: hostname(),
+            started: Local::now(),
+        };
+
+        assert!(stale_info.is_stale());
+        write_holder(&lock_path, &stale_info);
+
+        let refused: error::Result<LockGuard> = lock_file(&lock_path);
+
+        let guard: error::Result<LockGuard> = lock_file_with(
+            &lock_path,
+            &LockOptions::default().break_stale(true),
+        );
+
+        let info = guard.as_ref().ok().and_then(|g| read_lock_info(g));
+
+        assert!(refused.is_err());
+
+        let e = refused.unwrap_err();
+        assert_eq!(ErrorKind::LockFileExclusiveLock, *e.inner.get_context());
+        assert!(e.to_string().contains("stale: true"));
+
+        assert!(guard.is_ok());
+        assert!(info.is_some());
+        assert_eq!(process::id(), info.unwrap().pid);
+    }
+
+    #[test]
+    fn test_lock_file_other_host_holder() {
+        let dir = TempDir::new("lock_other_host_holder");
+        let lock_path = dir.join("app.lock");
+
+        // the pid may well be alive there, it cannot be checked from here
+        let remote_info = LockInfo {
+            pid: dead_pid(),
+            hostname: format!("{}-elsewhere", hostname()),
+            started: Local::now(),
+        };
+
+        assert!(!remote_info.is_stale());
+        write_holder(&lock_path, &remote_info);
+
+        let held: error::Result<LockGuard> = lock_file(&lock_path);
+
+        let shared: error::Result<LockGuard> =
+            lock_file_with(&lock_path, &LockOptions::default().shared(true));
+
+        let broken: error::Result<LockGuard> = lock_file_with(
+            &lock_path,
+            &LockOptions::default().break_stale(true),
+        );
+
+        let info = broken.as_ref().ok().and_then(|g| read_lock_info(g));
+
+        assert!(held.is_err());
+        assert!(shared.is_err());
+        assert!(broken.is_ok());
+        assert!(info.is_some());
+        assert!(info.unwrap().is_local());
+    }
+
+    #[test]
+    fn test_lock_file_live_holder_record() {
+        let dir = TempDir::new("lock_live_holder_record");
+        let lock_path = dir.join("app.lock");
+
+        let child = Command::new("sleep").arg("5").spawn();
+        assert!(child.is_ok());
+        let mut child = child.unwrap();
+
+        // recorded after the child started, as its own lock would be
+        let live_info = LockInfo {
+            pid: child.id(),
+            hostname: hostname(),
+            started: Local::now() + ::chrono::Duration::seconds(1),
+        };
+
+        // the same pid reused by a process started after the lock was taken
+        let reused_info = LockInfo {
+            started: Local::now() - ::chrono::Duration::hours(1),
+            ..live_info.clone()
+        };
+
+        assert!(live_info.is_alive());
+        assert!(reused_info.is_stale());
+
+        write_holder(&lock_path, &live_info);
+        let held: error::Result<LockGuard> = lock_file(&lock_path);
+
+        write_holder(&lock_path, &reused_info);
+        let reused: error::Result<LockGuard> = lock_file(&lock_path);
+
+        let broken: error::Result<LockGuard> = lock_file_with(
+            &lock_path,
+            &LockOptions::default().break_stale(true),
+        );
+
+        let _ = child.kill();
+        let _ = child.wait();
+
+        // a successful flock does not count on network file systems
+        assert!(held.is_err());
+        assert!(reused.is_err());
+        assert!(broken.is_ok());
+    }
+
+    #[test]
+    fn test_lock_guard_release_on_drop() {
+        let dir = TempDir::new("lock_guard_release");
+        let lock_path = dir.join("app.lock");
+
+        {
+            let guard: error::Result<LockGuard> = lock_file(&lock_path);
+            assert!(guard.is_ok());
+        }
+
+        let guard: error::Result<LockGuard> = lock_file_with(
+            &lock_path,
+            &LockOptions::default().remove_on_drop(true),
+        );
+
+        assert!(guard.is_ok());
+        drop(guard);
+        assert!(!lock_path.exists());
+    }
+
+    #[test]
+    fn test_is_locked_path() {
+        let dir = TempDir::new("is_locked_path");
+        let lock_path = dir.join("app.lock");
+
+        let flock = File::create(&lock_path);
+        assert!(flock.is_ok());
+        let flock = flock.unwrap();
+
+        let unchanged = is_locked_path(&flock, &lock_path);
+        assert!(unchanged.is_ok());
+        assert!(unchanged.unwrap());
+
+        assert!(fs::remove_file(&lock_path).is_ok());
+
+        let removed = is_locked_path(&flock, &lock_path);
+        assert!(removed.is_ok());
+        assert!(!removed.unwrap());
+
+        // a new file at the same path is a different lock
+        assert!(File::create(&lock_path).is_ok());
+
+        let replaced = is_locked_path(&flock, &lock_path);
+        assert!(replaced.is_ok());
+        assert!(!replaced.unwrap());
+    }
+
+    #[test]
+    fn test_lock_file_wait_timeout() {
+        let dir = TempDir::new("lock_wait_timeout");
+        let lock_path = dir.join("app.lock");
+
+        let first: error::Result<LockGuard> = lock_file(&lock_path);
+        assert!(first.is_ok());
+
+        let start = Instant::now();
+        let second: error::Result<LockGuard> =
+            lock_file_wait(&lock_path, Duration::from_millis(300));
+
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        assert!(second.is_err());
+        assert_eq!(
+            ErrorKind::LockFileWaitTimeout,
+            *second.unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_lock_file_shared() {
+        let dir = TempDir::new("lock_shared");
+        let lock_path = dir.join("app.lock");
+
+        let first: error::Result<LockGuard> = lock_file_shared(&lock_path);
+        let second: error::Result<LockGuard> = lock_file_shared(&lock_path);
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+
+        let exclusive: error::Result<LockGuard> = lock_file(&lock_path);
+        assert!(exclusive.is_err());
+    }
+
+    #[test]
+    fn test_write_to_file_atomic() {
+        let dir = TempDir::new("write_atomic");
+        let path = dir.join("out.txt");
+
+        let res: error::Result<()> = write_to_file_atomic(&path, "first");
+        assert!(res.is_ok());
+
+        let res: error::Result<()> = write_to_file_atomic(&path, b"second");
+        assert!(res.is_ok());
+
+        let contents: error::Result<String> = read_from_file(&path);
+
+        assert!(contents.is_ok());
+        assert_eq!("second", contents.unwrap());
+    }
+
+    #[test]
+    fn test_write_json_to_file_atomic() {
+        let dir = TempDir::new("write_json_atomic");
+        let path = dir.join("out.json");
+        let info = LockInfo::current();
+
+        let res: error::Result<()> = write_json_to_file_atomic(&path, &info);
+        assert!(res.is_ok());
+
+        let contents: error::Result<String> = read_from_file(&path);
+        assert!(contents.is_ok());
+
+        let read_info: Result<LockInfo, _> =
+            serde_json::from_str(&contents.unwrap());
+
+        assert!(read_info.is_ok());
+        assert_eq!(info, read_info.unwrap());
+    }
+
+    #[test]
+    fn test_write_to_file_atomic_missing_dir() {
+        let dir = TempDir::new("write_atomic_missing");
+        let path = dir.join("missing").join("nested");
+
+        let res: error::Result<()> = write_to_file_atomic(&path, "contents");
+        assert!(res.is_err());
+        assert_eq!(
+            ErrorKind::AtomicFileWrite,
+            *res.unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_local_storage() {
+        let storage: error::Result<Storage> = local_storage("/");
+        assert!(storage.is_ok());
+
+        let storage = storage.unwrap();
+        assert_eq!("/", storage.path());
+        assert!(*storage.capacity() >= *storage.used());
+        assert!(storage.available().is_some());
+    }
+
+    #[test]
+    fn test_local_storage_missing_path() {
+        let storage: error::Result<Storage> =
+            local_storage("/mega_coll/not/exist");
+
+        assert!(storage.is_err());
+        assert_eq!(
+            ErrorKind::Statvfs,
+            *storage.unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_local_storage_no_blocks() {
+        // procfs reports no blocks at all
+        let storage: error::Result<Storage> = local_storage("/proc");
+
+        assert!(storage.is_err());
+        assert_eq!(
+            ErrorKind::StatvfsNoBlocks,
+            *storage.unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_parse_and_filter_mounts() {
+        let content = "\
+            /dev/sda1 / ext4 rw,relatime 0 0\n\
+            proc /proc proc rw,nosuid 0 0\n\
+            tmpfs /run tmpfs rw,nosuid 0 0\n\
+            /dev/sdb1 /mnt/my\\040data xfs rw 0 0\n\
+            /dev/sda1 /var/lib/bind ext4 rw,relatime 0 0\n";
+
+        let mounts = parse_mounts(content);
+        assert_eq!(5, mounts.len());
+        assert_eq!("/mnt/my data", mounts[3].mount_path);
+
+        let real =
+            filter_mount_points(mounts.clone(), &MountsConfig::default());
+        let paths: Vec<&str> =
+            real.iter().map(|m| m.mount_path.as_str()).collect();
+
+        assert_eq!(vec!["/", "/mnt/my data", "/var/lib/bind"], paths);
+
+        let xfs_only = filter_mount_points(
+            mounts,
+            &MountsConfig {
+                include_fs_types: vec!["xfs".to_owned()],
+                exclude_fs_types: vec![],
+            },
+        );
+
+        assert_eq!(1, xfs_only.len());
+        assert_eq!("/dev/sdb1", xfs_only[0].device);
+    }
+
+    #[test]
+    fn test_dedup_mount_points() {
+        let mount = |device: &str, mount_path: &str| MountPoint {
+            device: device.to_owned(),
+            mount_path: mount_path.to_owned(),
+            fs_type: "tmpfs".to_owned(),
+            options: "rw".to_owned(),
+        };
+
+        let mounts = dedup_mount_points(vec![
+            mount("tmpfs", "/"),
+            mount("tmpfs", "/proc"),
+            mount("/dev/sda1", "/"),
+            mount("tmpfs", "/mega_coll/not/exist"),
+        ]);
+
+        // same device names but different file systems are all kept
+        let paths: Vec<&str> =
+            mounts.iter().map(|m| m.mount_path.as_str()).collect();
+
+        assert_eq!(vec!["/", "/proc", "/mega_coll/not/exist"], paths);
+    }
+}
@@ -0,0 +1,808 @@
+use chrono::NaiveDateTime;
+use conf::hdfs::DfConfig;
+use error::custom::{
+    MsgError, PermError, RegexCaptureError, RegexMinCaptureError,
+    TargetStringError,
+};
+use error::{Error, ErrorKind};
+use failure::{Fail, ResultExt};
+use json::{Storage, StorageBuilder};
+use regex::Regex;
+use std::fmt::Debug;
+use util::crc::CrcType;
+use util::process::{CommandExecutor, CommandOptions};
+
+const LS_DATE_TIME_FMT: &str = "%Y-%m-%d %H:%M";
+
+lazy_static! {
+    static ref DF_INITIAL_RE: Regex = Regex::new(
+        r"(?m)^Filesystem\s+Size\s+Used\s+Available\s+Use%[ \t]*\r?\n(.+)$"
+    )
+    .unwrap();
+
+    // sizes are either plain bytes or -h output such as "1.5 T" or "12G"
+    static ref DF_VALUES_RE: Regex = Regex::new(concat!(
+        r"^(\S+)\s+",
+        r"(\d+(?:\.\d+)?(?:\s?[KMGTPE])?)\s+",
+        r"(\d+(?:\.\d+)?(?:\s?[KMGTPE])?)\s+",
+        r"(\d+(?:\.\d+)?(?:\s?[KMGTPE])?)\s+",
+        r"(\d+(?:\.\d+)?)%$"
+    ))
+    .unwrap();
+
+    static ref LS_FOUND_RE: Regex = Regex::new(r"^Found \d+ items?$").unwrap();
+
+    // a trailing + on the permissions marks an ACL, paths may contain spaces
+    static ref LS_ENTRY_RE: Regex = Regex::new(concat!(
+        r"^([d-])([rwxsStT-]{9})(\+?)\s+",
+        r"(-|\d{1,9})\s+",
+        r"(\S+)\s+(\S+)\s+",
+        r"(\d+)\s+",
+        r"(\d{4}-\d{2}-\d{2} \d{2}:\d{2})\s+",
+        r"(.+)$"
+    ))
+    .unwrap();
+}
+
+const LS_ENTRY_CAP_LEN: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HdfsEntry {
+    pub perm: u16,
+    pub is_dir: bool,
+    pub has_acl: bool,
+    // directories have no replication factor
+    pub replication: Option<u32>,
+    pub owner: String,
+    pub group: String,
+    pub size: u64,
+    pub modified: NaiveDateTime,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HdfsChecksum {
+    pub path: String,
+    // e.g. COMPOSITE-CRC32C, or NONE for files without a checksum
+    pub algorithm: String,
+    pub hex: String,
+}
+
+impl HdfsChecksum {
+    // only composite CRCs are independent of block and chunk sizes, so the
+    // default MD5-of-MD5-of-CRC checksums cannot be recomputed locally
+    pub fn composite_crc(&self) -> Option<(CrcType, u32)> {
+        let crc_type = match self.algorithm.as_str() {
+            "COMPOSITE-CRC32" => CrcType::Crc32,
+            "COMPOSITE-CRC32C" => CrcType::Crc32c,
+            _ => return None,
+        };
+
+        if self.hex.len() != 8 {
+            return None;
+        }
+
+        u32::from_str_radix(&self.hex, 16)
+            .ok()
+            .map(|crc| (crc_type, crc))
+    }
+}
+
+pub type EntryStream<K> =
+    Box<Iterator<Item = Result<HdfsEntry, Error<K>>> + Send>;
+
+pub fn df<E, K>(
+    executor: &E,
+    program: &str,
+    conf: &DfConfig,
+) -> Result<Storage, Error<K>>
+where
+    E: CommandExecutor,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let output = executor
+        .run::<K, _>(
+            program,
+            &["dfs", "-df", conf.path.as_str()],
+            &CommandOptions::default(),
+        )
+        .context(ErrorKind::HdfsDfCmd)?;
+
+    let stdout = output
+        .into_stdout_str::<K>()
+        .context(ErrorKind::HdfsDfCmd)?;
+
+    parse_df(&conf.path, &stdout)
+}
+
+pub fn parse_df<K>(path: &str, output: &str) -> Result<Storage, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let values = DF_INITIAL_RE
+        .captures(output)
+        .and_then(|cap| cap.get(1))
+        .map(|values| values.as_str().trim())
+        .ok_or_else(|| RegexCaptureError::new(&DF_INITIAL_RE, output))
+        .context(ErrorKind::RegexInitialHdfsDfCap)?;
+
+    let cap = DF_VALUES_RE
+        .captures(values)
+        .ok_or_else(|| RegexCaptureError::new(&DF_VALUES_RE, values))
+        .context(ErrorKind::RegexHdfsDfValuesCap)?;
+
+    let capacity =
+        parse_df_size(&cap[2]).context(ErrorKind::ParseHdfsDfSizeValue)?;
+
+    let used =
+        parse_df_size(&cap[3]).context(ErrorKind::ParseHdfsDfUsedValue)?;
+
+    let available =
+        parse_df_size(&cap[4]).context(ErrorKind::ParseHdfsDfAvailableValue)?;
+
+    Ok(StorageBuilder::default()
+        .path(path)
+        .capacity(capacity)
+        .used(used)
+        .available(available)
+        .build())
+}
+
+pub fn parse_df_size(value: &str) -> Result<u64, TargetStringError<MsgError>> {
+    let to_err =
+        |msg: String| TargetStringError::new(value, MsgError::new(msg));
+
+    let (num, unit) = match value.chars().last() {
+        Some(c) if c.is_ascii_alphabetic() => {
+            (value[..value.len() - 1].trim(), Some(c))
+        }
+        _ => (value.trim(), None),
+    };
+
+    // -h output uses binary prefixes, e.g. 1 K is 1024 bytes
+    let exp = match unit {
+        None => return num.parse::<u64>().map_err(|e| to_err(e.to_string())),
+        Some('K') => 1,
+        Some('M') => 2,
+        Some('G') => 3,
+        Some('T') => 4,
+        Some('P') => 5,
+        Some('E') => 6,
+        Some(c) => return Err(to_err(format!("Unknown size unit '{}'", c))),
+    };
+
+    let num = num.parse::<f64>().map_err(|e| to_err(e.to_string()))?;
+    Ok((num * 1024f64.powi(exp)).round() as u64)
+}
+
+pub fn ls<E, K>(
+    executor: &E,
+    program: &str,
+    path: &str,
+    recursive: bool,
+) -> Result<EntryStream<K>, Error<K>>
+where
+    E: CommandExecutor,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let mut args = vec!["dfs", "-ls"];
+
+    if recursive {
+        args.push("-R");
+    }
+
+    args.push(path);
+
+    let lines = executor
+        .stream_lines::<K, _>(program, &args)
+        .context(ErrorKind::HdfsDfsLs)?;
+
+    // lines are parsed as they arrive so that -R listings stay streamed
+    let entries = lines.filter_map(|line| {
+        let line = match line.context(ErrorKind::HdfsDfsLs) {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e.into())),
+        };
+
+        match parse_ls_line(&line) {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    });
+
+    Ok(Box::new(entries))
+}
+
+pub fn parse_ls<K>(output: &str) -> Result<Vec<HdfsEntry>, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let mut entries = vec![];
+
+    for line in output.lines() {
+        if let Some(entry) = parse_ls_line(line)? {
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
+
+// None for the "Found N items" header and blank lines
+pub fn parse_ls_line<K>(line: &str) -> Result<Option<HdfsEntry>, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let line = line.trim_right();
+
+    if line.trim().is_empty() || LS_FOUND_RE.is_match(line) {
+        return Ok(None);
+    }
+
+    let cap = LS_ENTRY_RE
+        .captures(line)
+        .ok_or_else(|| {
+            RegexMinCaptureError::new(0, LS_ENTRY_CAP_LEN, line, &LS_ENTRY_RE)
+        })
+        .context(ErrorKind::RegexMinCapture)?;
+
+    let perm = parse_perm(&cap[2]).context(ErrorKind::RegexCapPerm)?;
+
+    let replication = match &cap[4] {
+        "-" => None,
+        // at most 9 digits, so always fits
+        r => r.parse::<u32>().ok(),
+    };
+
+    let size = cap[7]
+        .parse::<u64>()
+        .map_err(|e| TargetStringError::new(line, e))
+        .context(ErrorKind::RegexCapFileSizeParse)?;
+
+    let modified = NaiveDateTime::parse_from_str(&cap[8], LS_DATE_TIME_FMT)
+        .map_err(|e| TargetStringError::new(line, e))
+        .context(ErrorKind::NaiveDateTimeParse)?;
+
+    Ok(Some(HdfsEntry {
+        perm,
+        is_dir: &cap[1] == "d",
+        has_acl: &cap[3] == "+",
+        replication,
+        owner: cap[5].to_owned(),
+        group: cap[6].to_owned(),
+        size,
+        modified,
+        path: cap[9].to_owned(),
+    }))
+}
+
+// symbolic permissions such as "rwxr-x--T" into mode bits
+pub fn parse_perm(perm: &str) -> Result<u16, PermError> {
+    let chars: Vec<char> = perm.chars().collect();
+
+    if chars.len() != 9 {
+        return Err(PermError::new(perm));
+    }
+
+    let mut mode = 0u16;
+
+    for (i, c) in chars.iter().enumerate() {
+        let bit = 1 << (8 - i);
+
+        mode |= match (i % 3, *c) {
+            (_, '-') => 0,
+            (0, 'r') | (1, 'w') | (2, 'x') => bit,
+            // sticky bit, only valid in the others execute slot
+            (2, 't') if i == 8 => bit | 0o1000,
+            (2, 'T') if i == 8 => 0o1000,
+            _ => return Err(PermError::new(perm)),
+        };
+    }
+
+    Ok(mode)
+}
+
+pub fn checksum<E, K>(
+    executor: &E,
+    program: &str,
+    path: &str,
+) -> Result<HdfsChecksum, Error<K>>
+where
+    E: CommandExecutor,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    // the default combine mode is MD5 based, which cannot be checked locally
+    let output = executor
+        .run::<K, _>(
+            program,
+            &[
+                "dfs",
+                "-Ddfs.checksum.combine.mode=COMPOSITE_CRC",
+                "-checksum",
+                path,
+            ],
+            &CommandOptions::default(),
+        )
+        .context(ErrorKind::HdfsDfsChecksum)?;
+
+    let stdout = output
+        .into_stdout_str::<K>()
+        .context(ErrorKind::HdfsDfsChecksum)?;
+
+    parse_checksum(&stdout)
+}
+
+// the last "path<TAB>algorithm<TAB>hex" line, paths may contain spaces
+pub fn parse_checksum<K>(output: &str) -> Result<HdfsChecksum, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let checksum = output
+        .lines()
+        .rev()
+        .filter_map(|line| {
+            let mut parts = line.trim_right_matches('\r').rsplitn(3, '\t');
+
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(hex), Some(algorithm), Some(path)) => {
+                    Some(HdfsChecksum {
+                        path: path.to_owned(),
+                        algorithm: algorithm.to_owned(),
+                        hex: hex.to_owned(),
+                    })
+                }
+                _ => None,
+            }
+        })
+        .next()
+        .ok_or_else(|| {
+            TargetStringError::new(
+                output,
+                MsgError::new("No checksum line in output"),
+            )
+        })
+        .context(ErrorKind::ParseHdfsChecksum)?;
+
+    Ok(checksum)
+}
+
+// milliseconds since the epoch, -ls only shows the minute
+pub fn stat_modified<E, K>(
+    executor: &E,
+    program: &str,
+    path: &str,
+) -> Result<u64, Error<K>>
+where
+    E: CommandExecutor,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let output = executor
+        .run::<K, _>(
+            program,
+            &["dfs", "-stat", "%Y", path],
+            &CommandOptions::default(),
+        )
+        .context(ErrorKind::HdfsDfsStat)?;
+
+    let stdout = output
+        .into_stdout_str::<K>()
+        .context(ErrorKind::HdfsDfsStat)?;
+
+    parse_stat_modified(&stdout)
+}
+
+// one -stat for many paths saves starting a client per path, the output
+// holds a line per path in the given order
+pub fn stat_modified_all<E, K>(
+    executor: &E,
+    program: &str,
+    paths: &[&str],
+) -> Result<Vec<u64>, Error<K>>
+where
+    E: CommandExecutor,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let mut args = vec!["dfs", "-stat", "%Y"];
+    args.extend_from_slice(paths);
+
+    let output = executor
+        .run::<K, _>(program, &args, &CommandOptions::default())
+        .context(ErrorKind::HdfsDfsStat)?;
+
+    let stdout = output
+        .into_stdout_str::<K>()
+        .context(ErrorKind::HdfsDfsStat)?;
+
+    parse_stat_modified_all(&stdout, paths.len())
+}
+
+pub fn parse_stat_modified_all<K>(
+    output: &str,
+    count: usize,
+) -> Result<Vec<u64>, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let mut modified = vec![];
+
+    for line in output.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        modified.push(parse_stat_modified(line)?);
+    }
+
+    if modified.len() != count {
+        Err(MsgError::new(format!(
+            "Expected {} modification times, found {}",
+            count,
+            modified.len()
+        )))
+        .context(ErrorKind::ParseHdfsStat)?;
+    }
+
+    Ok(modified)
+}
+
+pub fn parse_stat_modified<K>(output: &str) -> Result<u64, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let line = output
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("");
+
+    let modified = line
+        .parse::<u64>()
+        .map_err(|e| TargetStringError::new(line, e))
+        .context(ErrorKind::ParseHdfsStat)?;
+
+    Ok(modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use error;
+    use std::thread;
+    use util::process::{ScriptedExecutor, ScriptedResponse};
+
+    const DF_BYTES: &str = "\
+Filesystem                        Size          Used     Available  Use%
+hdfs://namenode:8020  1099511627776  274877906944  769658139033   25%
+";
+
+    const DF_HUMAN: &str = "\
+Filesystem               Size     Used  Available  Use%
+hdfs://namenode:8020      1 T  256.5 G    700.8 G   25%
+";
+
+    const DF_HADOOP_3: &str = "\
+19/03/04 10:12:41 WARN util.NativeCodeLoader: Unable to load native-hadoop library
+Filesystem            Size   Used  Available  Use%
+hdfs://nameservice1   10T     2T         7T   20%
+";
+
+    #[test]
+    fn test_parse_df_bytes() {
+        let storage: error::Result<Storage> = parse_df("/", DF_BYTES);
+        assert!(storage.is_ok());
+
+        let storage = storage.unwrap();
+        assert_eq!("/", storage.path());
+        assert_eq!(1099511627776, *storage.capacity());
+        assert_eq!(274877906944, *storage.used());
+        assert_eq!(Some(769658139033), *storage.available());
+
+        // remainder not available to HDFS is non DFS used space
+        assert_eq!(Some(54_975_581_799), *storage.reserved());
+    }
+
+    #[test]
+    fn test_parse_df_human() {
+        let storage: error::Result<Storage> = parse_df("/data", DF_HUMAN);
+        assert!(storage.is_ok());
+
+        let storage = storage.unwrap();
+        assert_eq!(1u64 << 40, *storage.capacity());
+        assert_eq!((256.5 * (1u64 << 30) as f64) as u64, *storage.used());
+
+        let storage: error::Result<Storage> = parse_df("/", DF_HADOOP_3);
+        assert!(storage.is_ok());
+
+        let storage = storage.unwrap();
+        assert_eq!(10u64 << 40, *storage.capacity());
+        assert_eq!(2u64 << 40, *storage.used());
+        assert_eq!(Some(7u64 << 40), *storage.available());
+    }
+
+    #[test]
+    fn test_parse_df_malformed() {
+        let storage: error::Result<Storage> = parse_df("/", "");
+        assert_eq!(
+            ErrorKind::RegexInitialHdfsDfCap,
+            *storage.unwrap_err().inner.get_context()
+        );
+
+        let storage: error::Result<Storage> = parse_df(
+            "/",
+            "Filesystem Size Used Available Use%\nhdfs://nn 10 X 5 50%\n",
+        );
+
+        assert_eq!(
+            ErrorKind::RegexHdfsDfValuesCap,
+            *storage.unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_parse_df_size() {
+        for &(expected, size) in
+            &[(1234, "1234"), (1536, "1.5 K"), (3 << 20, "3M")]
+        {
+            let parsed = parse_df_size(size);
+            assert!(parsed.is_ok());
+
+            let parsed = parsed.unwrap();
+            assert_eq!(expected, parsed);
+        }
+
+        assert!(parse_df_size("1.5").is_err());
+        assert!(parse_df_size("1 Z").is_err());
+    }
+
+    #[test]
+    fn test_df() {
+        let executor = ScriptedExecutor::new().respond(
+            ScriptedResponse::new("hdfs", &["dfs", "-df", "/"])
+                .stdout(DF_BYTES),
+        );
+
+        let conf = DfConfig {
+            path: "/".to_owned(),
+        };
+
+        let storage: error::Result<Storage> = df(&executor, "hdfs", &conf);
+        assert!(storage.is_ok());
+
+        let storage = storage.unwrap();
+        assert_eq!(1099511627776, *storage.capacity());
+
+        // no scripted response for another path
+        let conf = DfConfig {
+            path: "/other".to_owned(),
+        };
+
+        let storage: error::Result<Storage> = df(&executor, "hdfs", &conf);
+        assert_eq!(
+            ErrorKind::HdfsDfCmd,
+            *storage.unwrap_err().inner.get_context()
+        );
+    }
+
+    const LS_OUTPUT: &str = "\
+Found 3 items
+drwxr-xr-x   - hdfs  supergroup          0 2019-03-01 10:12 /data/a
+-rw-r--r--   3 alice hadoop        1234567 2019-03-01 10:13 /data/file.csv
+drwxrwx--T+  - bob   analysts            0 2019-03-02 08:00 /data/acl dir
+";
+
+    #[test]
+    fn test_parse_ls() {
+        let entries: error::Result<Vec<HdfsEntry>> = parse_ls(LS_OUTPUT);
+        assert!(entries.is_ok());
+
+        let entries = entries.unwrap();
+        assert_eq!(3, entries.len());
+
+        let dir = &entries[0];
+        assert!(dir.is_dir);
+        assert!(!dir.has_acl);
+        assert_eq!(0o755, dir.perm);
+        assert_eq!(None, dir.replication);
+        assert_eq!("/data/a", dir.path);
+
+        let file = &entries[1];
+        assert!(!file.is_dir);
+        assert_eq!(0o644, file.perm);
+        assert_eq!(Some(3), file.replication);
+        assert_eq!("alice", file.owner);
+        assert_eq!("hadoop", file.group);
+        assert_eq!(1234567, file.size);
+        assert_eq!(
+            NaiveDateTime::parse_from_str("2019-03-01 10:13", LS_DATE_TIME_FMT)
+                .unwrap(),
+            file.modified
+        );
+
+        let acl_dir = &entries[2];
+        assert!(acl_dir.has_acl);
+        assert_eq!(0o1770, acl_dir.perm);
+        assert_eq!("/data/acl dir", acl_dir.path);
+    }
+
+    #[test]
+    fn test_parse_ls_line_malformed() {
+        let entry: error::Result<Option<HdfsEntry>> =
+            parse_ls_line("drwxr-xr-x - hdfs supergroup 0 /data/a");
+
+        assert_eq!(
+            ErrorKind::RegexMinCapture,
+            *entry.unwrap_err().inner.get_context()
+        );
+
+        let entry: error::Result<Option<HdfsEntry>> = parse_ls_line(
+            "-rw-r--r--   3 alice hadoop 1 2019-13-45 10:13 /data/bad",
+        );
+
+        assert_eq!(
+            ErrorKind::NaiveDateTimeParse,
+            *entry.unwrap_err().inner.get_context()
+        );
+
+        let entry: error::Result<Option<HdfsEntry>> = parse_ls_line(
+            "-rw-r--r--   3 alice hadoop 99999999999999999999 \
+             2019-03-01 10:13 /data/huge",
+        );
+
+        assert_eq!(
+            ErrorKind::RegexCapFileSizeParse,
+            *entry.unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_parse_perm() {
+        let perm = parse_perm("rwxr-x---");
+        assert!(perm.is_ok());
+
+        let perm = perm.unwrap();
+        assert_eq!(0o750, perm);
+
+        let perm = parse_perm("rwxrwxrwt");
+        assert!(perm.is_ok());
+
+        let perm = perm.unwrap();
+        assert_eq!(0o1777, perm);
+
+        assert!(parse_perm("rwxr-x--").is_err());
+        assert!(parse_perm("rwxr-xr-t").is_ok());
+        assert!(parse_perm("xwrr-x---").is_err());
+    }
+
+    #[test]
+    fn test_ls() {
+        let executor = ScriptedExecutor::new().respond(
+            ScriptedResponse::new("hdfs", &["dfs", "-ls", "-R", "/data"])
+                .stdout(LS_OUTPUT)
+                .stderr("ls: Permission denied: /data/private")
+                .code(1),
+        );
+
+        let entries: error::Result<EntryStream<ErrorKind>> =
+            ls(&executor, "hdfs", "/data", true);
+
+        assert!(entries.is_ok());
+
+        let entries = entries.unwrap();
+
+        // a stream can be handed to another thread
+        let entries = thread::spawn(move || {
+            entries.collect::<Vec<error::Result<HdfsEntry>>>()
+        })
+        .join();
+
+        assert!(entries.is_ok());
+        let entries = entries.unwrap();
+
+        // the listed entries, then the non-zero exit
+        assert_eq!(4, entries.len());
+        assert!(entries[..3].iter().all(|e| e.is_ok()));
+        assert_eq!(
+            ErrorKind::HdfsDfsLs,
+            *entries[3].as_ref().unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_parse_checksum() {
+        let checksum: error::Result<HdfsChecksum> =
+            parse_checksum("/data/a b.csv\tCOMPOSITE-CRC32C\te3069283\n");
+
+        assert!(checksum.is_ok());
+
+        let checksum = checksum.unwrap();
+        assert_eq!("/data/a b.csv", checksum.path);
+        assert_eq!(
+            Some((CrcType::Crc32c, 0xE306_9283)),
+            checksum.composite_crc()
+        );
+
+        let checksum: error::Result<HdfsChecksum> = parse_checksum(concat!(
+            "/data/a.csv\tMD5-of-0MD5-of-512CRC32C\t",
+            "000002000000000000000000a1b2c3d4e5f60718293a4b5c6d7e8f90\n",
+        ));
+
+        assert!(checksum.is_ok());
+
+        let checksum = checksum.unwrap();
+        assert_eq!(None, checksum.composite_crc());
+
+        let checksum: error::Result<HdfsChecksum> =
+            parse_checksum("/data/empty.csv\tNONE\t\n");
+
+        assert!(checksum.is_ok());
+
+        let checksum = checksum.unwrap();
+        assert_eq!(None, checksum.composite_crc());
+
+        let checksum: error::Result<HdfsChecksum> = parse_checksum("");
+        assert_eq!(
+            ErrorKind::ParseHdfsChecksum,
+            *checksum.unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_checksum() {
+        let executor = ScriptedExecutor::new().respond(
+            ScriptedResponse::new(
+                "hdfs",
+                &[
+                    "dfs",
+                    "-Ddfs.checksum.combine.mode=COMPOSITE_CRC",
+                    "-checksum",
+                    "/data/a.csv",
+                ],
+            )
+            .stderr("checksum: File does not exist: /data/a.csv")
+            .code(1),
+        );
+
+        let checksum: error::Result<HdfsChecksum> =
+            checksum(&executor, "hdfs", "/data/a.csv");
+
+        assert_eq!(
+            ErrorKind::HdfsDfsChecksum,
+            *checksum.unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_parse_stat_modified() {
+        let modified: error::Result<u64> =
+            parse_stat_modified("1551435180123\n");
+
+        assert!(modified.is_ok());
+
+        let modified = modified.unwrap();
+        assert_eq!(1_551_435_180_123, modified);
+
+        let modified: error::Result<u64> = parse_stat_modified("");
+        assert_eq!(
+            ErrorKind::ParseHdfsStat,
+            *modified.unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_parse_stat_modified_all() {
+        let modified: error::Result<Vec<u64>> =
+            parse_stat_modified_all("1551435180123\n1551435180456\n", 2);
+
+        assert!(modified.is_ok());
+
+        let modified = modified.unwrap();
+        assert_eq!(vec![1_551_435_180_123, 1_551_435_180_456], modified);
+
+        let modified: error::Result<Vec<u64>> =
+            parse_stat_modified_all("1551435180123\n", 2);
+
+        assert_eq!(
+            ErrorKind::ParseHdfsStat,
+            *modified.unwrap_err().inner.get_context()
+        );
+    }
+}
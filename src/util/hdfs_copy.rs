@@ -0,0 +1,1601 @@
+use chrono::format::{self, Parsed, StrftimeItems};
+use chrono::{Local, NaiveDate};
+use conf::hdfs::{CopyConfig, DateWindowConfig, VerifyConfig};
+use error::custom::{MsgError, PathError, TargetStringError};
+use error::{Error, ErrorKind};
+use failure::{Fail, ResultExt};
+use json::CopyManifest;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use regex::Regex;
+use serde_json;
+use std::cmp;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use util::crc::file_crc;
+use util::fs::{read_from_file, write_json_to_file_atomic};
+use util::hdfs::{
+    checksum, ls, stat_modified, stat_modified_all, EntryStream, HdfsEntry,
+};
+use util::process::{CommandExecutor, CommandOptions};
+
+// the manifest is rewritten after this many copies so that an interrupted
+// run does not have to start over
+const MANIFEST_SAVE_INTERVAL: usize = 100;
+
+// paths per -stat call, kept well below the argument length limit
+const STAT_BATCH_SIZE: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CopiedFile {
+    pub hdfs_path: String,
+    pub local_path: PathBuf,
+    pub size: u64,
+    // milliseconds since the epoch, only looked up for incremental copies
+    pub modified_millis: Option<u64>,
+    pub elapsed: Duration,
+    // copied a second time after failing verification
+    pub retried: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    // matched none of the configured regexes
+    NoMatch,
+    // a partition, or a year or month directory above partitions, with no
+    // dates in the window, directories are skipped without being listed
+    OutsideDateWindow,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedPath {
+    pub hdfs_path: String,
+    pub reason: SkipReason,
+}
+
+impl SkippedPath {
+    fn new(hdfs_path: String, reason: SkipReason) -> SkippedPath {
+        SkippedPath { hdfs_path, reason }
+    }
+}
+
+#[derive(Debug)]
+pub struct CopyFailure<K>
+where
+    K: Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    pub hdfs_path: String,
+    pub error: Error<K>,
+}
+
+#[derive(Debug)]
+pub struct CopySummary<K>
+where
+    K: Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    pub copied: Vec<CopiedFile>,
+    pub skipped: Vec<SkippedPath>,
+    // recorded in the manifest as already copied
+    pub unchanged: Vec<String>,
+    pub failed: Vec<CopyFailure<K>>,
+    // listing problems, e.g. a sub directory without read permission
+    pub list_errors: Vec<Error<K>>,
+}
+
+impl<K> CopySummary<K>
+where
+    K: Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    fn new() -> CopySummary<K> {
+        CopySummary {
+            copied: vec![],
+            skipped: vec![],
+            unchanged: vec![],
+            failed: vec![],
+            list_errors: vec![],
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty() && self.list_errors.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub struct DateFilter {
+    re: Regex,
+    format: String,
+    from: NaiveDate,
+    to: NaiveDate,
+}
+
+impl DateFilter {
+    pub fn new<K>(
+        conf: &DateWindowConfig,
+        today: NaiveDate,
+    ) -> Result<DateFilter, Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+    {
+        let re =
+            Regex::new(&conf.pattern).context(ErrorKind::HdfsRegexMatch)?;
+
+        if re.captures_len() < 2 {
+            Err(MsgError::new(format!(
+                "Date pattern {} has no capture group",
+                conf.pattern
+            )))
+            .context(ErrorKind::InvalidDateTarget)?;
+        }
+
+        let (from, to) = conf
+            .window
+            .bounds(today)
+            .ok_or_else(|| {
+                MsgError::new(format!("Empty date window {:?}", conf.window))
+            })
+            .context(ErrorKind::InvalidDateTarget)?;
+
+        Ok(DateFilter {
+            re,
+            format: conf.format.clone(),
+            from,
+            to,
+        })
+    }
+
+    pub fn from(&self) -> &NaiveDate {
+        &self.from
+    }
+
+    pub fn to(&self) -> &NaiveDate {
+        &self.to
+    }
+
+    // None when the path holds no date, e.g. a parent of the partitions
+    pub fn path_date<K>(
+        &self,
+        path: &str,
+    ) -> Result<Option<NaiveDate>, Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+    {
+        let date_str = match self.re.captures(path).and_then(|cap| cap.get(1)) {
+            Some(date_str) => date_str.as_str(),
+            None => return Ok(None),
+        };
+
+        let date = NaiveDate::parse_from_str(date_str, &self.format)
+            .map_err(|e| TargetStringError::new(path, e))
+            .context(ErrorKind::HashDateTimeParse)?;
+
+        Ok(Some(date))
+    }
+
+    pub fn contains(&self, date: &NaiveDate) -> bool {
+        self.from <= *date && *date <= self.to
+    }
+
+    // the dates below a directory named by a leading part of the format,
+    // e.g. all of October for 2026/10 and %Y/%m/%d, None if not named so
+    pub fn partial_date_range(
+        &self,
+        path: &str,
+    ) -> Option<(NaiveDate, NaiveDate)> {
+        let format_parts: Vec<&str> = self.format.split('/').collect();
+        let path_parts: Vec<&str> =
+            path.trim_right_matches('/').split('/').collect();
+
+        for len in (1..format_parts.len()).rev() {
+            if len > path_parts.len() {
+                continue;
+            }
+
+            let partial_format = format_parts[..len].join("/");
+            let text = path_parts[path_parts.len() - len..].join("/");
+
+            let mut parsed = Parsed::new();
+            let items = StrftimeItems::new(&partial_format);
+
+            if format::parse(&mut parsed, &text, items).is_err() {
+                continue;
+            }
+
+            let range = match (parsed.year, parsed.month) {
+                (Some(year), Some(month)) => month_range(year, month),
+                (Some(year), None) => year_range(year),
+                _ => None,
+            };
+
+            // names that only parse loosely, e.g. 7 as %Y, are no dates
+            match range {
+                Some((first, _))
+                    if first.format(&partial_format).to_string() == text =>
+                {
+                    return range
+                }
+                _ => (),
+            }
+        }
+
+        None
+    }
+}
+
+fn year_range(year: i32) -> Option<(NaiveDate, NaiveDate)> {
+    Some((
+        NaiveDate::from_ymd_opt(year, 1, 1)?,
+        NaiveDate::from_ymd_opt(year, 12, 31)?,
+    ))
+}
+
+fn month_range(year: i32, month: u32) -> Option<(NaiveDate, NaiveDate)> {
+    let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let last = (28..32)
+        .rev()
+        .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .next()?;
+
+    Some((first, last))
+}
+
+// the transfers themselves cannot be throttled, so a token bucket shared
+// by the concurrent copies is charged with the size of each file before it
+// starts, which keeps the bytes started by all of them within the rate plus
+// a burst of one second
+#[derive(Debug)]
+struct RateLimiter {
+    bytes_per_sec: u64,
+    // when the bucket is full again given everything charged so far
+    full_at: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    fn new(bytes_per_sec: u64) -> RateLimiter {
+        RateLimiter {
+            bytes_per_sec,
+            full_at: Mutex::new(None),
+        }
+    }
+
+    fn wait(&self, size: u64) {
+        let owed = {
+            let mut full_at = match self.full_at.lock() {
+                Ok(full_at) => full_at,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+
+            let now = Instant::now();
+
+            let charged_from = match *full_at {
+                Some(full_at) if full_at > now => full_at,
+                _ => now,
+            };
+
+            // in nanoseconds so that small files add up instead of each
+            // rounding down to nothing
+            let nanos = u128::from(size) * 1_000_000_000
+                / u128::from(self.bytes_per_sec);
+
+            let charged_to = charged_from
+                + Duration::from_nanos(cmp::min(
+                    nanos,
+                    u128::from(std::u64::MAX),
+                ) as u64);
+
+            *full_at = Some(charged_to);
+            charged_to - now
+        };
+
+        // the bucket holds a second of bytes, anything owed beyond that is
+        // waited out before the transfer starts
+        let burst = Duration::from_secs(1);
+
+        if owed > burst {
+            thread::sleep(owed - burst);
+        }
+    }
+}
+
+// shared by the concurrent copies, which record themselves as they finish
+#[derive(Debug)]
+struct ManifestRecorder {
+    path: PathBuf,
+    // the manifest and the number of copies recorded since the last save
+    state: Mutex<(CopyManifest, usize)>,
+}
+
+impl ManifestRecorder {
+    fn new(path: &Path, manifest: CopyManifest) -> ManifestRecorder {
+        ManifestRecorder {
+            path: path.to_owned(),
+            state: Mutex::new((manifest, 0)),
+        }
+    }
+
+    fn record<K>(&self, entry: &HdfsEntry, copied: &CopiedFile)
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+    {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let (ref mut manifest, ref mut unsaved) = *state;
+
+        manifest.record(
+            entry.path.clone(),
+            entry.size,
+            entry.modified,
+            copied.modified_millis,
+        );
+
+        *unsaved += 1;
+
+        // the final save after all copies reports its error instead
+        if *unsaved >= MANIFEST_SAVE_INTERVAL {
+            *unsaved = 0;
+
+            if let Err(e) =
+                write_json_to_file_atomic::<_, _, K>(&self.path, &*manifest)
+            {
+                warn!("Unable to save manifest: {}", e);
+            }
+        }
+    }
+
+    fn into_manifest(self) -> CopyManifest {
+        match self.state.into_inner() {
+            Ok(state) => state.0,
+            Err(poisoned) => poisoned.into_inner().0,
+        }
+    }
+}
+
+pub fn copy_to_local<E, K>(
+    executor: &E,
+    program: &str,
+    conf: &CopyConfig,
+) -> Result<CopySummary<K>, Error<K>>
+where
+    E: CommandExecutor + Sync,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let matches = conf
+        .matches
+        .iter()
+        .map(|m| Regex::new(m))
+        .collect::<Result<Vec<_>, _>>()
+        .context(ErrorKind::HdfsRegexMatch)?;
+
+    let manifest_path = manifest_path(&conf.copy_to);
+
+    let manifest = if conf.incremental {
+        Some(load_manifest(&manifest_path)?)
+    } else {
+        None
+    };
+
+    let mut summary = CopySummary::new();
+    let mut to_copy = vec![];
+
+    let today = Local::now().naive_local().date();
+
+    let entries = list_sources::<E, K>(
+        executor,
+        program,
+        conf,
+        today,
+        &mut summary.skipped,
+    )?;
+
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("Unable to list entry under {}: {}", conf.path, e);
+                summary.list_errors.push(e);
+                continue;
+            }
+        };
+
+        if entry.is_dir {
+            continue;
+        }
+
+        // no matches means everything under the path is copied
+        if !matches.is_empty()
+            && !matches.iter().any(|m| m.is_match(&entry.path))
+        {
+            summary
+                .skipped
+                .push(SkippedPath::new(entry.path, SkipReason::NoMatch));
+            continue;
+        }
+
+        // the listing only has minutes, so a match is confirmed against the
+        // recorded milliseconds before the copy is skipped, entries without
+        // them are copied once more
+        let recorded_millis = match manifest {
+            Some(ref manifest)
+                if manifest.is_unchanged(
+                    &entry.path,
+                    entry.size,
+                    &entry.modified,
+                ) && (!conf.verify_size
+                    || local_size_matches(conf, &entry)) =>
+            {
+                manifest
+                    .get(&entry.path)
+                    .and_then(|recorded| *recorded.modified_millis())
+            }
+            _ => None,
+        };
+
+        to_copy.push((entry, recorded_millis));
+    }
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(conf.concurrency.unwrap_or(1))
+        .build()
+        .context(ErrorKind::CopyThreadPoolBuild)?;
+
+    // looked up before any copy, so that a change during the copy is seen
+    // as a change on the next run
+    let modified: Vec<Result<Option<u64>, Error<K>>> = if conf.incremental {
+        let paths: Vec<&str> =
+            to_copy.iter().map(|copy| copy.0.path.as_str()).collect();
+
+        stat_entries(executor, program, &paths)
+            .into_iter()
+            .map(|modified| modified.map(Some))
+            .collect()
+    } else {
+        to_copy.iter().map(|_| Ok(None)).collect()
+    };
+
+    let limiter = conf.max_bytes_per_sec.map(RateLimiter::new);
+    let recorder = manifest
+        .map(|manifest| ManifestRecorder::new(&manifest_path, manifest));
+
+    // every copy runs to completion, failures are only collected
+    let results: Vec<Result<Option<CopiedFile>, Error<K>>> =
+        pool.install(|| {
+            to_copy
+                .par_iter()
+                .zip(modified)
+                .map(|(&(ref entry, recorded_millis), modified_millis)| {
+                    let result = modified_millis.and_then(|modified_millis| {
+                        copy_changed(
+                            executor,
+                            program,
+                            entry,
+                            recorded_millis,
+                            modified_millis,
+                            conf,
+                            limiter.as_ref(),
+                        )
+                    });
+
+                    // only successful copies are recorded, failures are
+                    // retried on the next run
+                    match (&result, &recorder) {
+                        (Ok(Some(copied)), Some(recorder)) => {
+                            recorder.record::<K>(entry, copied)
+                        }
+                        (Err(e), _) => {
+                            warn!("Unable to copy {}: {}", entry.path, e)
+                        }
+                        _ => (),
+                    }
+
+                    result
+                })
+                .collect()
+        });
+
+    for ((entry, _), result) in to_copy.iter().zip(results) {
+        match result {
+            Ok(None) => summary.unchanged.push(entry.path.clone()),
+            Ok(Some(copied)) => summary.copied.push(copied),
+            Err(e) => summary.failed.push(CopyFailure {
+                hdfs_path: entry.path.clone(),
+                error: e,
+            }),
+        }
+    }
+
+    if let Some(recorder) = recorder {
+        write_json_to_file_atomic(&manifest_path, &recorder.into_manifest())?;
+    }
+
+    Ok(summary)
+}
+
+fn list_sources<E, K>(
+    executor: &E,
+    program: &str,
+    conf: &CopyConfig,
+    today: NaiveDate,
+    skipped: &mut Vec<SkippedPath>,
+) -> Result<EntryStream<K>, Error<K>>
+where
+    E: CommandExecutor,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    match conf.date_window {
+        Some(ref date_window) => {
+            let filter = DateFilter::new(date_window, today)?;
+            let entries = list_date_window(
+                executor, program, &conf.path, &filter, skipped,
+            );
+            Ok(Box::new(entries.into_iter()))
+        }
+        None => ls(executor, program, &conf.path, true),
+    }
+}
+
+// directories are only descended into until a date shows up in their path,
+// so partitions outside of the window are never listed, and neither are
+// directories such as 2025 or 2026/09 of a %Y/%m/%d layout that cannot
+// lead into the window
+fn list_date_window<E, K>(
+    executor: &E,
+    program: &str,
+    root: &str,
+    filter: &DateFilter,
+    skipped: &mut Vec<SkippedPath>,
+) -> Vec<Result<HdfsEntry, Error<K>>>
+where
+    E: CommandExecutor,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let mut entries = vec![];
+    let mut dirs = VecDeque::new();
+    dirs.push_back(root.to_owned());
+
+    while let Some(dir) = dirs.pop_front() {
+        let listed = match ls::<E, K>(executor, program, &dir, false) {
+            Ok(listed) => listed,
+            Err(e) => {
+                entries.push(Err(e));
+                continue;
+            }
+        };
+
+        for entry in listed {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    entries.push(Err(e));
+                    continue;
+                }
+            };
+
+            match filter.path_date::<K>(&entry.path) {
+                Ok(Some(ref date)) if !filter.contains(date) => skipped.push(
+                    SkippedPath::new(entry.path, SkipReason::OutsideDateWindow),
+                ),
+                Ok(Some(_)) if entry.is_dir => {
+                    match ls::<E, K>(executor, program, &entry.path, true) {
+                        Ok(partition) => entries.extend(partition),
+                        Err(e) => entries.push(Err(e)),
+                    }
+                }
+                Ok(Some(_)) => entries.push(Ok(entry)),
+                Ok(None) if entry.is_dir => {
+                    match filter.partial_date_range(&entry.path) {
+                        Some((first, last))
+                            if last < filter.from || first > filter.to =>
+                        {
+                            skipped.push(SkippedPath::new(
+                                entry.path,
+                                SkipReason::OutsideDateWindow,
+                            ))
+                        }
+                        _ => dirs.push_back(entry.path),
+                    }
+                }
+                // files outside of any partition
+                Ok(None) => (),
+                Err(e) => entries.push(Err(e)),
+            }
+        }
+    }
+
+    entries
+}
+
+// kept next to rather than inside copy_to so it is never mistaken for data
+pub fn manifest_path(copy_to: &str) -> PathBuf {
+    let copy_to = match copy_to.trim_right_matches('/') {
+        "" => "/",
+        copy_to => copy_to,
+    };
+
+    PathBuf::from(format!("{}.manifest.json", copy_to))
+}
+
+pub fn load_manifest<P, K>(path: P) -> Result<CopyManifest, Error<K>>
+where
+    P: AsRef<Path>,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let path = path.as_ref();
+
+    if !path.exists() {
+        return Ok(CopyManifest::default());
+    }
+
+    let manifest = serde_json::from_str(&read_from_file::<_, K>(path)?)
+        .map_err(|e| PathError::new(path, e))
+        .context(ErrorKind::CopyManifestParse)?;
+
+    Ok(manifest)
+}
+
+fn local_size_matches(conf: &CopyConfig, entry: &HdfsEntry) -> bool {
+    let meta = local_path::<_, ErrorKind>(&conf.copy_to, &entry.path)
+        .ok()
+        .and_then(|path| fs::metadata(path).ok());
+
+    match meta {
+        Some(meta) => meta.len() == entry.size,
+        None => false,
+    }
+}
+
+pub fn local_path<P, K>(
+    copy_to: P,
+    hdfs_path: &str,
+) -> Result<PathBuf, Error<K>>
+where
+    P: AsRef<Path>,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let rel_path = Path::new(hdfs_path)
+        .strip_prefix("/")
+        .map_err(|e| PathError::new(hdfs_path, e))
+        .context(ErrorKind::StripRootPath)?;
+
+    Ok(copy_to.as_ref().join(rel_path))
+}
+
+// a batch that fails, say on a file removed since the listing, is looked
+// up again path by path so that only that path fails
+fn stat_entries<E, K>(
+    executor: &E,
+    program: &str,
+    paths: &[&str],
+) -> Vec<Result<u64, Error<K>>>
+where
+    E: CommandExecutor,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let mut modified = Vec::with_capacity(paths.len());
+
+    for batch in paths.chunks(STAT_BATCH_SIZE) {
+        match stat_modified_all::<E, K>(executor, program, batch) {
+            Ok(batch_modified) => {
+                modified.extend(batch_modified.into_iter().map(Ok))
+            }
+            Err(e) => {
+                warn!("Unable to stat {} paths at once: {}", batch.len(), e);
+
+                modified.extend(
+                    batch
+                        .iter()
+                        .map(|path| stat_modified(executor, program, path)),
+                )
+            }
+        }
+    }
+
+    modified
+}
+
+// None if the modification time still matches the recorded one
+fn copy_changed<E, K>(
+    executor: &E,
+    program: &str,
+    entry: &HdfsEntry,
+    recorded_millis: Option<u64>,
+    modified_millis: Option<u64>,
+    conf: &CopyConfig,
+    limiter: Option<&RateLimiter>,
+) -> Result<Option<CopiedFile>, Error<K>>
+where
+    E: CommandExecutor,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    if recorded_millis.is_some() && recorded_millis == modified_millis {
+        return Ok(None);
+    }
+
+    if let Some(limiter) = limiter {
+        limiter.wait(entry.size);
+    }
+
+    let mut copied = copy_entry(executor, program, entry, conf)?;
+    copied.modified_millis = modified_millis;
+
+    info!(
+        "Copied {} to {:?} in {:?}",
+        copied.hdfs_path, copied.local_path, copied.elapsed
+    );
+
+    Ok(Some(copied))
+}
+
+fn copy_entry<E, K>(
+    executor: &E,
+    program: &str,
+    entry: &HdfsEntry,
+    conf: &CopyConfig,
+) -> Result<CopiedFile, Error<K>>
+where
+    E: CommandExecutor,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let start = Instant::now();
+    let local_path = local_path(&conf.copy_to, &entry.path)?;
+
+    if let Some(parent) = local_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| PathError::new(parent, e))
+            .context(ErrorKind::DirsCreate)?;
+    }
+
+    run_copy(executor, program, &entry.path, &local_path)?;
+    let mut retried = false;
+
+    if let Some(ref verify) = conf.verify {
+        let mut mismatch =
+            verify_copy(executor, program, entry, &local_path, verify)?;
+
+        if mismatch.is_some() && verify.retry {
+            warn!("Copy of {} does not match, retrying once", entry.path);
+
+            run_copy(executor, program, &entry.path, &local_path)?;
+            mismatch =
+                verify_copy(executor, program, entry, &local_path, verify)?;
+            retried = true;
+        }
+
+        if let Some(mismatch) = mismatch {
+            Err(mismatch).context(ErrorKind::CopyVerifyMismatch)?;
+        }
+    }
+
+    Ok(CopiedFile {
+        hdfs_path: entry.path.clone(),
+        local_path,
+        size: entry.size,
+        modified_millis: None,
+        elapsed: start.elapsed(),
+        retried,
+    })
+}
+
+fn run_copy<E, K>(
+    executor: &E,
+    program: &str,
+    hdfs_path: &str,
+    local_path: &Path,
+) -> Result<(), Error<K>>
+where
+    E: CommandExecutor,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let local_path_str = local_path.to_string_lossy();
+
+    // -f so that a file left over from an earlier run is overwritten
+    executor
+        .run::<K, _>(
+            program,
+            &["dfs", "-copyToLocal", "-f", hdfs_path, &local_path_str],
+            &CommandOptions::default(),
+        )
+        .context(ErrorKind::HdfsCopyToLocal)?;
+
+    Ok(())
+}
+
+// Ok(Some(..)) describes a mismatch, errors are for failing to check at all
+fn verify_copy<E, K>(
+    executor: &E,
+    program: &str,
+    entry: &HdfsEntry,
+    local_path: &Path,
+    verify: &VerifyConfig,
+) -> Result<Option<TargetStringError<MsgError>>, Error<K>>
+where
+    E: CommandExecutor,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let mismatch = |msg: String| {
+        Ok(Some(TargetStringError::new(
+            entry.path.as_str(),
+            MsgError::new(msg),
+        )))
+    };
+
+    let local_size = fs::metadata(local_path)
+        .map_err(|e| PathError::new(local_path, e))
+        .context(ErrorKind::FileIo)?
+        .len();
+
+    if local_size != entry.size {
+        return mismatch(format!(
+            "Local size {} differs from HDFS length {}",
+            local_size, entry.size
+        ));
+    }
+
+    if !verify.checksum {
+        return Ok(None);
+    }
+
+    let hdfs_checksum = checksum::<E, K>(executor, program, &entry.path)?;
+
+    let (crc_type, hdfs_crc) = match hdfs_checksum.composite_crc() {
+        Some(crc) => crc,
+        None => {
+            debug!(
+                "Skipping checksum of {}, {} cannot be computed locally",
+                entry.path, hdfs_checksum.algorithm
+            );
+
+            return Ok(None);
+        }
+    };
+
+    let local_crc = file_crc::<_, K>(crc_type, local_path)?;
+
+    if local_crc != hdfs_crc {
+        return mismatch(format!(
+            "Local {:?} {:08x} differs from HDFS {:08x}",
+            crc_type, local_crc, hdfs_crc
+        ));
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use conf::hdfs::DateWindow;
+    use error;
+    use std::sync::Arc;
+    use util::hdfs::parse_ls;
+    use util::process::{ScriptedExecutor, ScriptedResponse};
+    use util::test_util::TempDir;
+
+    const LS_OUTPUT: &str = "\
+drwxr-xr-x   - hdfs  supergroup    0 2019-03-01 10:12 /data/a
+-rw-r--r--   3 alice hadoop       10 2019-03-01 10:13 /data/a/one.csv
+-rw-r--r--   3 alice hadoop       20 2019-03-01 10:13 /data/a/two.csv
+-rw-r--r--   3 alice hadoop       30 2019-03-01 10:13 /data/a/notes.txt
+";
+
+    fn copy_args(hdfs_path: &str, copy_to: &Path) -> Vec<String> {
+        let local: error::Result<PathBuf> = local_path(copy_to, hdfs_path);
+        assert!(local.is_ok());
+
+        let local = local.unwrap();
+
+        vec![
+            "dfs".to_owned(),
+            "-copyToLocal".to_owned(),
+            "-f".to_owned(),
+            hdfs_path.to_owned(),
+            local.to_string_lossy().into_owned(),
+        ]
+    }
+
+    #[test]
+    fn test_copy_to_local() {
+        let dir = TempDir::new("copy_to_local");
+        let copy_to = dir.join("copy_to");
+
+        let executor = ScriptedExecutor::new()
+            .respond(
+                ScriptedResponse::new("hdfs", &["dfs", "-ls", "-R", "/data"])
+                    .stdout(LS_OUTPUT),
+            )
+            .respond(ScriptedResponse::new(
+                "hdfs",
+                &copy_args("/data/a/one.csv", &copy_to),
+            ))
+            .respond(
+                ScriptedResponse::new(
+                    "hdfs",
+                    &copy_args("/data/a/two.csv", &copy_to),
+                )
+                .stderr("copyToLocal: Connection refused")
+                .code(1),
+            );
+
+        let conf = CopyConfig {
+            path: "/data".to_owned(),
+            matches: vec![r"\.csv$".to_owned()],
+            copy_to: copy_to.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+
+        let summary: error::Result<CopySummary<ErrorKind>> =
+            copy_to_local(&executor, "hdfs", &conf);
+
+        assert!(summary.is_ok());
+        let summary = summary.unwrap();
+
+        assert!(!summary.is_success());
+        assert_eq!(1, summary.copied.len());
+        assert_eq!(
+            copy_to.join("data/a/one.csv"),
+            summary.copied[0].local_path
+        );
+        assert_eq!(
+            vec![SkippedPath::new(
+                "/data/a/notes.txt".to_owned(),
+                SkipReason::NoMatch
+            )],
+            summary.skipped
+        );
+
+        // the failure does not stop the remaining copies
+        assert_eq!(1, summary.failed.len());
+        assert_eq!("/data/a/two.csv", summary.failed[0].hdfs_path);
+        assert_eq!(
+            ErrorKind::HdfsCopyToLocal,
+            *summary.failed[0].error.inner.get_context()
+        );
+
+        assert!(copy_to.join("data/a").is_dir());
+    }
+
+    #[test]
+    fn test_copy_to_local_bad_regex() {
+        let executor = ScriptedExecutor::new();
+
+        let conf = CopyConfig {
+            path: "/data".to_owned(),
+            matches: vec![r"(unclosed".to_owned()],
+            copy_to: "/tmp".to_owned(),
+            ..Default::default()
+        };
+
+        let summary: error::Result<CopySummary<ErrorKind>> =
+            copy_to_local(&executor, "hdfs", &conf);
+
+        assert_eq!(
+            ErrorKind::HdfsRegexMatch,
+            *summary.unwrap_err().inner.get_context()
+        );
+
+        assert!(executor.calls().is_empty());
+    }
+
+    #[test]
+    fn test_copy_to_local_parallel() {
+        let dir = TempDir::new("copy_to_local_parallel");
+        let copy_to = dir.join("copy_to");
+
+        let executor = ScriptedExecutor::new()
+            .respond(
+                ScriptedResponse::new("hdfs", &["dfs", "-ls", "-R", "/data"])
+                    .stdout(LS_OUTPUT),
+            )
+            .respond(ScriptedResponse::new(
+                "hdfs",
+                &copy_args("/data/a/one.csv", &copy_to),
+            ))
+            .respond(
+                ScriptedResponse::new(
+                    "hdfs",
+                    &copy_args("/data/a/two.csv", &copy_to),
+                )
+                .code(1),
+            )
+            .respond(ScriptedResponse::new(
+                "hdfs",
+                &copy_args("/data/a/notes.txt", &copy_to),
+            ));
+
+        let conf = CopyConfig {
+            path: "/data".to_owned(),
+            copy_to: copy_to.to_string_lossy().into_owned(),
+            concurrency: Some(4),
+            max_bytes_per_sec: Some(1_000_000),
+            ..Default::default()
+        };
+
+        let summary: error::Result<CopySummary<ErrorKind>> =
+            copy_to_local(&executor, "hdfs", &conf);
+
+        assert!(summary.is_ok());
+        let summary = summary.unwrap();
+
+        // results keep the listing order regardless of completion order
+        let copied: Vec<&str> = summary
+            .copied
+            .iter()
+            .map(|c| c.hdfs_path.as_str())
+            .collect();
+
+        assert_eq!(vec!["/data/a/one.csv", "/data/a/notes.txt"], copied);
+        assert_eq!(1, summary.failed.len());
+        assert_eq!("/data/a/two.csv", summary.failed[0].hdfs_path);
+        assert_eq!(4, executor.calls().len());
+    }
+
+    fn checksum_args(hdfs_path: &str) -> Vec<String> {
+        vec![
+            "dfs".to_owned(),
+            "-Ddfs.checksum.combine.mode=COMPOSITE_CRC".to_owned(),
+            "-checksum".to_owned(),
+            hdfs_path.to_owned(),
+        ]
+    }
+
+    #[test]
+    fn test_copy_to_local_verify_checksum() {
+        let dir = TempDir::new("copy_to_local_verify_checksum");
+        let copy_to = dir.join("copy_to");
+        let one = "/data/a/one.csv";
+        let two = "/data/a/two.csv";
+
+        // the scripted copies leave these in place
+        assert!(fs::create_dir_all(copy_to.join("data/a")).is_ok());
+        let one_local = copy_to.join("data/a/one.csv");
+        let two_local = copy_to.join("data/a/two.csv");
+        assert!(fs::write(&one_local, b"123456789").is_ok());
+        assert!(fs::write(&two_local, b"123456780").is_ok());
+
+        let executor = ScriptedExecutor::new()
+            .respond(
+                ScriptedResponse::new("hdfs", &["dfs", "-ls", "-R", "/data"])
+                    .stdout(concat!(
+                        "-rw-r--r--   3 alice hadoop 9 2019-03-01 10:13 ",
+                        "/data/a/one.csv\n",
+                        "-rw-r--r--   3 alice hadoop 9 2019-03-01 10:13 ",
+                        "/data/a/two.csv\n",
+                    )),
+            )
+            .respond(ScriptedResponse::new("hdfs", &copy_args(one, &copy_to)))
+            .respond(ScriptedResponse::new("hdfs", &copy_args(two, &copy_to)))
+            .respond(
+                ScriptedResponse::new("hdfs", &checksum_args(one))
+                    .stdout("/data/a/one.csv\tCOMPOSITE-CRC32C\te3069283\n"),
+            )
+            .respond(
+                ScriptedResponse::new("hdfs", &checksum_args(two))
+                    .stdout("/data/a/two.csv\tCOMPOSITE-CRC32C\te3069283\n"),
+            );
+
+        let conf = CopyConfig {
+            path: "/data".to_owned(),
+            copy_to: copy_to.to_string_lossy().into_owned(),
+            verify: Some(VerifyConfig {
+                checksum: true,
+                retry: true,
+            }),
+            ..Default::default()
+        };
+
+        let summary: error::Result<CopySummary<ErrorKind>> =
+            copy_to_local(&executor, "hdfs", &conf);
+
+        assert!(summary.is_ok());
+        let summary = summary.unwrap();
+
+        assert_eq!(1, summary.copied.len());
+        assert_eq!(one, summary.copied[0].hdfs_path);
+        assert!(!summary.copied[0].retried);
+
+        assert_eq!(1, summary.failed.len());
+        assert_eq!(two, summary.failed[0].hdfs_path);
+        assert_eq!(
+            ErrorKind::CopyVerifyMismatch,
+            *summary.failed[0].error.inner.get_context()
+        );
+
+        // the mismatching file is copied and checked once more
+        let two_copies = executor
+            .calls()
+            .iter()
+            .filter(|c| c.args == copy_args(two, &copy_to))
+            .count();
+
+        assert_eq!(2, two_copies);
+    }
+
+    #[test]
+    fn test_copy_to_local_verify_size() {
+        let dir = TempDir::new("copy_to_local_verify_size");
+        let copy_to = dir.join("copy_to");
+
+        assert!(fs::create_dir_all(copy_to.join("data/a")).is_ok());
+        let one_local = copy_to.join("data/a/one.csv");
+        assert!(fs::write(&one_local, b"123456789").is_ok());
+
+        let executor = ScriptedExecutor::new()
+            .respond(
+                ScriptedResponse::new("hdfs", &["dfs", "-ls", "-R", "/data"])
+                    .stdout(concat!(
+                        "-rw-r--r--   3 alice hadoop 10 2019-03-01 10:13 ",
+                        "/data/a/one.csv\n",
+                    )),
+            )
+            .respond(ScriptedResponse::new(
+                "hdfs",
+                &copy_args("/data/a/one.csv", &copy_to),
+            ));
+
+        let conf = CopyConfig {
+            path: "/data".to_owned(),
+            copy_to: copy_to.to_string_lossy().into_owned(),
+            verify: Some(VerifyConfig::default()),
+            ..Default::default()
+        };
+
+        let summary: error::Result<CopySummary<ErrorKind>> =
+            copy_to_local(&executor, "hdfs", &conf);
+
+        assert!(summary.is_ok());
+        let summary = summary.unwrap();
+
+        assert!(summary.copied.is_empty());
+        assert_eq!(
+            ErrorKind::CopyVerifyMismatch,
+            *summary.failed[0].error.inner.get_context()
+        );
+
+        // no retry and no checksum requested
+        assert_eq!(2, executor.calls().len());
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(1000);
+        let start = Instant::now();
+
+        // the first transfer uses up the burst, the second waits for its
+        // own 100 bytes
+        limiter.wait(1000);
+        limiter.wait(100);
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_rate_limiter_concurrent() {
+        let limiter = Arc::new(RateLimiter::new(1000));
+        let start = Instant::now();
+
+        // parallel starts share the bucket, 1200 bytes at 1000 a second
+        // with a second of burst
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let limiter = limiter.clone();
+                thread::spawn(move || limiter.wait(300))
+            })
+            .collect();
+
+        for handle in handles {
+            assert!(handle.join().is_ok());
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_rate_limiter_small_files() {
+        let limiter = RateLimiter::new(1_000_000);
+        let start = Instant::now();
+
+        // each file is half of the budget of a millisecond, past the burst
+        // of a second
+        for _ in 0..2200 {
+            limiter.wait(500);
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_manifest_recorder() {
+        let dir = TempDir::new("manifest_recorder");
+        let copy_to = dir.join("copy_to");
+        let path = manifest_path(&copy_to.to_string_lossy());
+
+        let entries: error::Result<Vec<HdfsEntry>> = parse_ls(LS_OUTPUT);
+        assert!(entries.is_ok());
+
+        let entries = entries.unwrap();
+        let entry = &entries[1];
+
+        let copied = CopiedFile {
+            hdfs_path: entry.path.clone(),
+            local_path: copy_to.join("data/a/one.csv"),
+            size: entry.size,
+            modified_millis: Some(1_551_435_180_123),
+            elapsed: Duration::from_secs(0),
+            retried: false,
+        };
+
+        let recorder = ManifestRecorder::new(&path, CopyManifest::default());
+
+        for _ in 1..MANIFEST_SAVE_INTERVAL {
+            recorder.record::<ErrorKind>(entry, &copied);
+        }
+
+        assert!(!path.exists());
+
+        // saved while copies are still running
+        recorder.record::<ErrorKind>(entry, &copied);
+        assert!(path.exists());
+
+        let manifest = recorder.into_manifest();
+        assert_eq!(1, manifest.files().len());
+    }
+
+    #[test]
+    fn test_local_path() {
+        let path: error::Result<PathBuf> =
+            local_path("/backup", "/data/a/one.csv");
+
+        assert!(path.is_ok());
+
+        let path = path.unwrap();
+        assert_eq!(PathBuf::from("/backup/data/a/one.csv"), path);
+
+        let path: error::Result<PathBuf> =
+            local_path("/backup", "hdfs://nn/data/a/one.csv");
+
+        assert_eq!(
+            ErrorKind::StripRootPath,
+            *path.unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_copy_to_local_incremental() {
+        let dir = TempDir::new("copy_to_local_incremental");
+        let copy_to = dir.join("copy_to");
+        let ls_args = ["dfs", "-ls", "-R", "/data"];
+
+        let stat_args =
+            ["dfs", "-stat", "%Y", "/data/a/one.csv", "/data/a/two.csv"];
+
+        // two.csv keeps its modification time throughout
+        let executor_for = |ls_stdout: String, one_millis: &str| {
+            ScriptedExecutor::new()
+                .respond(
+                    ScriptedResponse::new("hdfs", &ls_args).stdout(ls_stdout),
+                )
+                .respond(
+                    ScriptedResponse::new("hdfs", &stat_args)
+                        .stdout(format!("{}\n1551435180456\n", one_millis)),
+                )
+                .respond(ScriptedResponse::new(
+                    "hdfs",
+                    &copy_args("/data/a/one.csv", &copy_to),
+                ))
+                .respond(ScriptedResponse::new(
+                    "hdfs",
+                    &copy_args("/data/a/two.csv", &copy_to),
+                ))
+        };
+
+        let mut conf = CopyConfig {
+            path: "/data".to_owned(),
+            matches: vec![r"\.csv$".to_owned()],
+            copy_to: copy_to.to_string_lossy().into_owned(),
+            incremental: true,
+            ..Default::default()
+        };
+
+        // first run copies everything and records it
+        let executor = executor_for(LS_OUTPUT.to_owned(), "1551435180123");
+
+        let summary: error::Result<CopySummary<ErrorKind>> =
+            copy_to_local(&executor, "hdfs", &conf);
+
+        assert!(summary.is_ok());
+
+        let summary = summary.unwrap();
+        assert_eq!(2, summary.copied.len());
+
+        // both files are looked up in a single call
+        let stat_calls = executor
+            .calls()
+            .into_iter()
+            .filter(|call| call.args[1] == "-stat")
+            .count();
+
+        assert_eq!(1, stat_calls);
+
+        let manifest: error::Result<CopyManifest> =
+            load_manifest(manifest_path(&conf.copy_to));
+
+        assert!(manifest.is_ok());
+
+        let manifest = manifest.unwrap();
+        assert_eq!(2, manifest.files().len());
+        assert_eq!(
+            Some(1_551_435_180_123),
+            *manifest.files()["/data/a/one.csv"].modified_millis()
+        );
+
+        // second run only copies the file whose size changed
+        let resized = LS_OUTPUT.replace("   20 2019", "   21 2019");
+        let executor = executor_for(resized.clone(), "1551435180123");
+
+        let summary: error::Result<CopySummary<ErrorKind>> =
+            copy_to_local(&executor, "hdfs", &conf);
+
+        assert!(summary.is_ok());
+
+        let summary = summary.unwrap();
+        assert_eq!(vec!["/data/a/one.csv"], summary.unchanged);
+        assert_eq!(1, summary.copied.len());
+        assert_eq!("/data/a/two.csv", summary.copied[0].hdfs_path);
+
+        // a rewrite within the same minute and with the same size only
+        // shows in the milliseconds
+        let executor = executor_for(resized, "1551435180789");
+
+        let summary: error::Result<CopySummary<ErrorKind>> =
+            copy_to_local(&executor, "hdfs", &conf);
+
+        assert!(summary.is_ok());
+
+        let summary = summary.unwrap();
+        assert_eq!(vec!["/data/a/two.csv"], summary.unchanged);
+        assert_eq!(1, summary.copied.len());
+        assert_eq!("/data/a/one.csv", summary.copied[0].hdfs_path);
+
+        // local files are never written by the scripted executor, so size
+        // verification finds them missing and copies them again
+        conf.verify_size = true;
+
+        let executor = executor_for(LS_OUTPUT.to_owned(), "1551435180789");
+
+        let summary: error::Result<CopySummary<ErrorKind>> =
+            copy_to_local(&executor, "hdfs", &conf);
+
+        assert!(summary.is_ok());
+
+        let summary = summary.unwrap();
+        assert!(summary.unchanged.is_empty());
+        assert_eq!(2, summary.copied.len());
+
+        // a failed batch falls back to one lookup per file, so a file that
+        // cannot be looked up only fails its own copy
+        let executor = ScriptedExecutor::new()
+            .respond(
+                ScriptedResponse::new("hdfs", &ls_args)
+                    .stdout(LS_OUTPUT.to_owned()),
+            )
+            .respond(
+                ScriptedResponse::new(
+                    "hdfs",
+                    &["dfs", "-stat", "%Y", "/data/a/two.csv"],
+                )
+                .stdout("1551435180456\n"),
+            )
+            .respond(ScriptedResponse::new(
+                "hdfs",
+                &copy_args("/data/a/two.csv", &copy_to),
+            ));
+
+        let summary: error::Result<CopySummary<ErrorKind>> =
+            copy_to_local(&executor, "hdfs", &conf);
+
+        assert!(summary.is_ok());
+
+        let summary = summary.unwrap();
+        assert_eq!(1, summary.failed.len());
+        assert_eq!("/data/a/one.csv", summary.failed[0].hdfs_path);
+        assert_eq!(
+            ErrorKind::HdfsDfsStat,
+            *summary.failed[0].error.inner.get_context()
+        );
+        assert_eq!(1, summary.copied.len());
+        assert_eq!("/data/a/two.csv", summary.copied[0].hdfs_path);
+    }
+
+    #[test]
+    fn test_manifest_path() {
+        assert_eq!(
+            PathBuf::from("/backup/data.manifest.json"),
+            manifest_path("/backup/data/")
+        );
+
+        assert_eq!(PathBuf::from("/.manifest.json"), manifest_path("/"));
+    }
+
+    #[test]
+    fn test_date_filter() {
+        let conf = DateWindowConfig {
+            pattern: r"dt=(\d{4}-\d{2}-\d{2})".to_owned(),
+            format: "%Y-%m-%d".to_owned(),
+            window: DateWindow::LastDays(3),
+        };
+
+        let today = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        let filter: error::Result<DateFilter> = DateFilter::new(&conf, today);
+        assert!(filter.is_ok());
+
+        let filter = filter.unwrap();
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2026, 10, 16).unwrap(),
+            *filter.from()
+        );
+        assert_eq!(today, *filter.to());
+
+        let date: error::Result<Option<NaiveDate>> =
+            filter.path_date("/data/dt=2026-10-17/part-0000");
+
+        assert!(date.is_ok());
+
+        let date = date.unwrap();
+        assert!(date.is_some());
+        assert!(filter.contains(&date.unwrap()));
+
+        let date: error::Result<Option<NaiveDate>> = filter.path_date("/data");
+        assert!(date.is_ok());
+
+        let date = date.unwrap();
+        assert_eq!(None, date);
+
+        let date: error::Result<Option<NaiveDate>> =
+            filter.path_date("/data/dt=2026-13-45");
+
+        assert_eq!(
+            ErrorKind::HashDateTimeParse,
+            *date.unwrap_err().inner.get_context()
+        );
+
+        let conf = DateWindowConfig {
+            window: DateWindow::LastDays(0),
+            ..conf
+        };
+
+        let filter: error::Result<DateFilter> = DateFilter::new(&conf, today);
+        assert_eq!(
+            ErrorKind::InvalidDateTarget,
+            *filter.unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_copy_to_local_date_window() {
+        let dir = TempDir::new("copy_to_local_date_window");
+        let copy_to = dir.join("copy_to");
+
+        let dir = |path: &str| {
+            format!("drwxr-xr-x - h h 0 2026-10-17 00:00 {}\n", path)
+        };
+
+        let ls_dir = |path: &str, stdout: String| {
+            ScriptedResponse::new("hdfs", &["dfs", "-ls", path]).stdout(stdout)
+        };
+
+        // yyyy/mm/dd layout, only 2026/10/17 is inside the window, 2025 and
+        // 2026/09 are not listed as they have no responses
+        let executor = ScriptedExecutor::new()
+            .respond(ls_dir(
+                "/data",
+                dir("/data/2025") + &dir("/data/2026") + &dir("/data/tmp"),
+            ))
+            .respond(ls_dir(
+                "/data/2026",
+                dir("/data/2026/09") + &dir("/data/2026/10"),
+            ))
+            .respond(ls_dir("/data/tmp", String::new()))
+            .respond(ls_dir(
+                "/data/2026/10",
+                dir("/data/2026/10/16") + &dir("/data/2026/10/17"),
+            ))
+            .respond(
+                ScriptedResponse::new(
+                    "hdfs",
+                    &["dfs", "-ls", "-R", "/data/2026/10/17"],
+                )
+                .stdout(
+                    "-rw-r--r-- 3 h h 5 2026-10-17 01:00 /data/2026/10/17/x",
+                ),
+            )
+            .respond(ScriptedResponse::new(
+                "hdfs",
+                &copy_args("/data/2026/10/17/x", &copy_to),
+            ));
+
+        let conf = CopyConfig {
+            path: "/data".to_owned(),
+            copy_to: copy_to.to_string_lossy().into_owned(),
+            date_window: Some(DateWindowConfig {
+                pattern: r"/(\d{4}/\d{2}/\d{2})(?:/|$)".to_owned(),
+                format: "%Y/%m/%d".to_owned(),
+                window: DateWindow::Range {
+                    from: NaiveDate::from_ymd_opt(2026, 10, 17).unwrap(),
+                    to: NaiveDate::from_ymd_opt(2026, 10, 17).unwrap(),
+                },
+            }),
+            ..Default::default()
+        };
+
+        let summary: error::Result<CopySummary<ErrorKind>> =
+            copy_to_local(&executor, "hdfs", &conf);
+
+        assert!(summary.is_ok());
+
+        let summary = summary.unwrap();
+        assert!(summary.is_success());
+        assert_eq!(1, summary.copied.len());
+        assert_eq!("/data/2026/10/17/x", summary.copied[0].hdfs_path);
+
+        let skipped: Vec<(&str, SkipReason)> = summary
+            .skipped
+            .iter()
+            .map(|s| (s.hdfs_path.as_str(), s.reason))
+            .collect();
+
+        assert_eq!(
+            vec![
+                ("/data/2025", SkipReason::OutsideDateWindow),
+                ("/data/2026/09", SkipReason::OutsideDateWindow),
+                ("/data/2026/10/16", SkipReason::OutsideDateWindow),
+            ],
+            skipped
+        );
+
+        // /data, /data/2026, /data/tmp, /data/2026/10 and the partition
+        let ls_calls = executor
+            .calls()
+            .iter()
+            .filter(|call| call.args[1] == "-ls")
+            .count();
+
+        assert_eq!(5, ls_calls);
+    }
+
+    #[test]
+    fn test_partial_date_range() {
+        let conf = DateWindowConfig {
+            pattern: r"/(\d{4}/\d{2}/\d{2})(?:/|$)".to_owned(),
+            format: "%Y/%m/%d".to_owned(),
+            window: DateWindow::Yesterday,
+        };
+
+        let today = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        let filter: error::Result<DateFilter> = DateFilter::new(&conf, today);
+        assert!(filter.is_ok());
+
+        let filter = filter.unwrap();
+        let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+
+        assert_eq!(
+            Some((date(1, 1), date(12, 31))),
+            filter.partial_date_range("/data/2024")
+        );
+
+        assert_eq!(
+            Some((date(2, 1), date(2, 29))),
+            filter.partial_date_range("/data/2024/02/")
+        );
+
+        assert_eq!(None, filter.partial_date_range("/data"));
+        assert_eq!(None, filter.partial_date_range("/data/7"));
+    }
+}
@@ -0,0 +1,103 @@
+use conf::krb5::{Auth, Config};
+use error::{Error, ErrorKind};
+use failure::{Fail, ResultExt};
+use std::fmt::Debug;
+use util::process::{CommandExecutor, CommandOptions, Secret};
+
+pub fn kinit<E, K>(
+    executor: &E,
+    program: &str,
+    conf: &Config,
+    opts: &CommandOptions,
+) -> Result<(), Error<K>>
+where
+    E: CommandExecutor,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    match conf.auth {
+        // the password goes through stdin only, never argv or a shell
+        Auth::Password(ref pw) => {
+            executor
+                .run_with_secret::<K, _>(
+                    program,
+                    &[conf.login.as_str()],
+                    Secret::line(pw.as_bytes()),
+                    opts,
+                )
+                .context(ErrorKind::KinitPw)?;
+        }
+
+        Auth::Keytab(ref keytab) => {
+            executor
+                .run::<K, _>(
+                    program,
+                    &["-kt", keytab.as_ref(), conf.login.as_str()],
+                    opts,
+                )
+                .context(ErrorKind::KinitKeytab)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use error;
+    use std::borrow::Cow;
+    use util::process::{ScriptedExecutor, ScriptedResponse};
+
+    #[test]
+    fn test_kinit_password() {
+        let executor = ScriptedExecutor::new().respond(
+            ScriptedResponse::new("kinit", &["alice"])
+                .stderr("kinit: Password incorrect: hunter2")
+                .code(1),
+        );
+
+        let conf = Config {
+            login: "alice".to_owned(),
+            auth: Auth::Password(Cow::Borrowed("hunter2")),
+        };
+
+        let res: error::Result<()> =
+            kinit(&executor, "kinit", &conf, &CommandOptions::default());
+
+        assert!(res.is_err());
+
+        let err = res.unwrap_err();
+        assert_eq!(ErrorKind::KinitPw, *err.inner.get_context());
+        assert!(!err.to_string().contains("hunter2"));
+
+        let calls = executor.calls();
+        assert_eq!(vec!["alice"], calls[0].args);
+        assert_eq!(Some(b"<redacted>\n".to_vec()), calls[0].stdin);
+
+        for call in &calls {
+            let stdin = call.stdin.clone().unwrap_or_default();
+            assert!(!String::from_utf8_lossy(&stdin).contains("hunter2"));
+            assert!(!call.args.iter().any(|arg| arg.contains("hunter2")));
+        }
+    }
+
+    #[test]
+    fn test_kinit_keytab() {
+        let executor = ScriptedExecutor::new().respond(ScriptedResponse::new(
+            "kinit",
+            &["-kt", "/etc/alice.keytab", "alice"],
+        ));
+
+        let conf = Config {
+            login: "alice".to_owned(),
+            auth: Auth::Keytab(Cow::Borrowed("/etc/alice.keytab")),
+        };
+
+        let res: error::Result<()> =
+            kinit(&executor, "kinit", &conf, &CommandOptions::default());
+
+        assert!(res.is_ok());
+        assert_eq!(None, executor.calls()[0].stdin);
+    }
+}
@@ -1,3 +1,12 @@
 pub mod app;
+pub mod conf;
+pub mod crc;
+pub mod du;
 pub mod fs;
+pub mod hdfs;
+pub mod hdfs_copy;
+pub mod krb5;
 pub mod process;
+
+#[cfg(test)]
+mod test_util;
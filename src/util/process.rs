@@ -1,9 +1,78 @@
-use error::custom::{CodeMsgError, MsgError};
+use chrono::{DateTime, Local, TimeZone};
+use error::custom::{CodeMsgError, MsgError, ProgramNotFoundError};
 use error::{Error, ErrorKind};
 use failure::{Context, Fail, ResultExt};
-use std::fmt::Debug;
-use std::io::Read;
-use std::process::{Child, ChildStdout, Output};
+use libc;
+use std::cmp;
+use std::collections::HashMap;
+use std::env;
+use std::fmt::{self, Debug};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::marker::PhantomData;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, ExitStatus, Output, Stdio};
+use std::ptr;
+use std::sync::atomic::{self, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const COMMAND_POLL_INTERVAL_MS: u64 = 20;
+const PIPE_DRAIN_GRACE_MS: u64 = 500;
+const REDACTED: &str = "<redacted>";
+
+lazy_static! {
+    static ref WHICH_CACHE: Mutex<HashMap<(String, Vec<PathBuf>), PathBuf>> =
+        Mutex::new(HashMap::new());
+}
+
+#[derive(Default, Debug)]
+pub struct CommandOptions {
+    pub timeout: Option<Duration>,
+    pub stdin: Option<Vec<u8>>,
+}
+
+impl CommandOptions {
+    pub fn timeout<T>(mut self, timeout: T) -> CommandOptions
+    where
+        T: Into<Option<Duration>>,
+    {
+        self.timeout = timeout.into();
+        self
+    }
+
+    pub fn stdin<B>(mut self, stdin: B) -> CommandOptions
+    where
+        B: Into<Vec<u8>>,
+    {
+        self.stdin = Some(stdin.into());
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct CommandOutput {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub elapsed: Duration,
+}
+
+impl CommandOutput {
+    pub fn into_stdout_str<K>(self) -> Result<String, Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+    {
+        let stdout = String::from_utf8(self.stdout)
+            .context(ErrorKind::StdoutUtf8Conversion)?;
+
+        Ok(stdout)
+    }
+}
 
 pub fn extract_child_stdout<K>(child: Child) -> Result<ChildStdout, Error<K>>
 where
@@ -51,3 +120,1397 @@ where
 
     Ok(output)
 }
+
+pub fn hostname() -> String {
+    let mut buf = [0u8; 256];
+
+    let ret = unsafe {
+        libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len())
+    };
+
+    if ret != 0 {
+        return "unknown".to_owned();
+    }
+
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).into_owned()
+}
+
+pub fn pid_alive(pid: u32) -> bool {
+    // signal 0 only checks for existence and permission to signal
+    let ret = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    ret == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+// None where /proc is not available, e.g. on hosts other than Linux
+pub fn process_started(pid: u32) -> Option<DateTime<Local>> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+
+    // the command name before the fields may itself contain spaces and ')'
+    let fields: Vec<&str> =
+        stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+
+    // starttime is the 22nd field, in clock ticks since boot
+    let start_ticks = fields.get(19)?.parse::<u64>().ok()?;
+
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+
+    if ticks_per_sec <= 0 {
+        return None;
+    }
+
+    let ticks_per_sec = ticks_per_sec as u64;
+
+    let boot_secs = fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find(|line| line.starts_with("btime "))?
+        .split_whitespace()
+        .nth(1)?
+        .parse::<i64>()
+        .ok()?;
+
+    let secs = boot_secs + (start_ticks / ticks_per_sec) as i64;
+    let nanos = (start_ticks % ticks_per_sec) * 1_000_000_000 / ticks_per_sec;
+
+    Local.timestamp_opt(secs, nanos as u32).single()
+}
+
+pub struct Secret {
+    buf: Vec<u8>,
+}
+
+impl Secret {
+    pub fn new<B>(secret: B) -> Secret
+    where
+        B: AsRef<[u8]>,
+    {
+        Secret {
+            buf: secret.as_ref().to_vec(),
+        }
+    }
+
+    // for prompts such as kinit that read the secret as a single line,
+    // sized up front so that no partial copy is left behind by a realloc
+    pub fn line<B>(secret: B) -> Secret
+    where
+        B: AsRef<[u8]>,
+    {
+        let secret = secret.as_ref();
+        let mut buf = Vec::with_capacity(secret.len() + 1);
+        buf.extend_from_slice(secret);
+        buf.push(b'\n');
+
+        Secret { buf }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret({})", REDACTED)
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        for b in self.buf.iter_mut() {
+            // volatile so that zeroing a buffer about to be freed is kept
+            unsafe { ptr::write_volatile(b, 0) };
+        }
+
+        atomic::compiler_fence(Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug)]
+pub struct CommandLines<K> {
+    cmd_line: String,
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    stderr_reader: Option<Receiver<io::Result<Vec<u8>>>>,
+    buf: Vec<u8>,
+    done: bool,
+    _kind: PhantomData<K>,
+}
+
+impl<K> CommandLines<K>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    fn finish(&mut self) -> Option<Result<String, Error<K>>> {
+        self.done = true;
+
+        let status = match self.child.wait() {
+            Ok(status) => status,
+            Err(e) => {
+                let err = CodeMsgError::new(None, e.to_string())
+                    .with_cmd(self.cmd_line.clone());
+
+                return Some(Err(err.context(ErrorKind::CommandWait).into()));
+            }
+        };
+
+        let stderr = match join_thread(self.stderr_reader.take()) {
+            Ok(stderr) => stderr.unwrap_or_default(),
+            Err(e) => {
+                return Some(Err(e
+                    .context(ErrorKind::CommandOutputRead)
+                    .into()))
+            }
+        };
+
+        if status.success() {
+            None
+        } else {
+            let err = exit_error(self.cmd_line.clone(), status, &stderr);
+            Some(Err(err.context(ErrorKind::ChildOutput).into()))
+        }
+    }
+}
+
+impl<K> Iterator for CommandLines<K>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    type Item = Result<String, Error<K>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        // the buffer is reused so memory stays bounded by the longest line
+        self.buf.clear();
+
+        match self.stdout.read_until(b'\n', &mut self.buf) {
+            Ok(0) => self.finish(),
+            Ok(_) => {
+                if self.buf.ends_with(b"\n") {
+                    self.buf.pop();
+                }
+
+                if self.buf.ends_with(b"\r") {
+                    self.buf.pop();
+                }
+
+                Some(
+                    String::from_utf8(self.buf.clone())
+                        .context(ErrorKind::StdoutUtf8Conversion)
+                        .map_err(|e| e.into()),
+                )
+            }
+            Err(e) => {
+                // nothing more is read, so the child is not left a zombie
+                self.done = true;
+                kill_process_group(self.child.id());
+                let _ = self.child.wait();
+
+                Some(Err(e.context(ErrorKind::CommandOutputRead).into()))
+            }
+        }
+    }
+}
+
+impl<K> Drop for CommandLines<K> {
+    fn drop(&mut self) {
+        // stopped early, so the child may still be blocked writing to stdout
+        if !self.done {
+            kill_process_group(self.child.id());
+            let _ = self.child.wait();
+        }
+    }
+}
+
+pub fn command_line(cmd: &Command) -> String {
+    format!("{:?}", cmd)
+}
+
+pub fn run_command<K>(
+    cmd: &mut Command,
+    opts: &CommandOptions,
+) -> Result<CommandOutput, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let stdin = opts.stdin.as_ref().map(Secret::new);
+    run_command_stdin(cmd, stdin, false, opts.timeout)
+}
+
+// the secret only ever reaches the child through its stdin pipe, it is
+// zeroed once written and scrubbed from stderr in any returned error
+pub fn run_command_with_secret<K>(
+    cmd: &mut Command,
+    secret: Secret,
+    opts: &CommandOptions,
+) -> Result<CommandOutput, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    run_command_stdin(cmd, Some(secret), true, opts.timeout)
+}
+
+fn run_command_stdin<K>(
+    cmd: &mut Command,
+    stdin: Option<Secret>,
+    redact_stdin: bool,
+    timeout: Option<Duration>,
+) -> Result<CommandOutput, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let cmd_line = command_line(cmd);
+    let stdin = stdin.map(Arc::new);
+
+    let stdin_pipe = if stdin.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    };
+
+    // own process group so that a timeout also takes down grandchildren,
+    // e.g. the java process spawned by the hdfs wrapper script
+    in_own_process_group(cmd)
+        .stdin(stdin_pipe)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let start = Instant::now();
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| {
+            CodeMsgError::new(None, e.to_string()).with_cmd(cmd_line.clone())
+        })
+        .context(ErrorKind::CommandSpawn)?;
+
+    let stdin_writer = match (child.stdin.take(), stdin.clone()) {
+        (Some(mut pipe), Some(payload)) => Some(spawn_with_result(move || {
+            match pipe.write_all(payload.as_bytes()) {
+                // the child is free to exit without reading all of its input
+                Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                r => r,
+            }
+        })),
+        _ => None,
+    };
+
+    // both pipes are drained concurrently so that neither can fill up and
+    // block the child
+    let stdout_reader = child.stdout.take().map(spawn_pipe_reader);
+    let stderr_reader = child.stderr.take().map(spawn_pipe_reader);
+
+    let status = wait_child(&mut child, timeout)
+        .map_err(|e| {
+            CodeMsgError::new(None, e.to_string()).with_cmd(cmd_line.clone())
+        })
+        .context(ErrorKind::CommandWait)?;
+
+    // a descendant that left the process group, e.g. via setsid, survives
+    // the kill and can hold the pipes open indefinitely
+    let deadline = timeout.map(|timeout| {
+        cmp::max(start + timeout, Instant::now())
+            + Duration::from_millis(PIPE_DRAIN_GRACE_MS)
+    });
+
+    let stdout = join_thread_until(stdout_reader, deadline);
+    let stderr = join_thread_until(stderr_reader, deadline);
+    let stdin_written = join_thread_until(stdin_writer, deadline);
+
+    // after a timeout whatever stderr could still be read is good enough
+    let stderr = match (status, stderr) {
+        (None, Err(_)) => vec![],
+        (_, stderr) => stderr
+            .context(ErrorKind::CommandOutputRead)?
+            .unwrap_or_default(),
+    };
+
+    let stderr_scrubbed = match stdin {
+        Some(ref secret) if redact_stdin => redact(&stderr, secret.as_bytes()),
+        _ => stderr.clone(),
+    };
+
+    let status = match status {
+        Some(status) => status,
+        None => {
+            let stderr_msg =
+                String::from_utf8_lossy(&stderr_scrubbed).into_owned();
+
+            let err = CodeMsgError::new(None, stderr_msg)
+                .with_cmd(cmd_line)
+                .with_signal(libc::SIGKILL);
+
+            return Err(err.context(ErrorKind::CommandTimeout).into());
+        }
+    };
+
+    if !status.success() {
+        Err(exit_error(cmd_line, status, &stderr_scrubbed))
+            .context(ErrorKind::ChildOutput)?;
+    }
+
+    let stdout = stdout
+        .context(ErrorKind::CommandOutputRead)?
+        .unwrap_or_default();
+
+    stdin_written.context(ErrorKind::CommandStdinWrite)?;
+
+    Ok(CommandOutput {
+        status,
+        stdout,
+        stderr,
+        elapsed: start.elapsed(),
+    })
+}
+
+pub fn stream_command_lines<K>(
+    cmd: &mut Command,
+) -> Result<CommandLines<K>, Error<K>>
+where
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let cmd_line = command_line(cmd);
+
+    in_own_process_group(cmd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| {
+            CodeMsgError::new(None, e.to_string()).with_cmd(cmd_line.clone())
+        })
+        .context(ErrorKind::CommandSpawn)?;
+
+    let stdout = child.stdout.take();
+    let stderr_reader = child.stderr.take().map(spawn_pipe_reader);
+
+    let stdout = match stdout {
+        Some(stdout) => stdout,
+        None => {
+            kill_process_group(child.id());
+            let _ = child.wait();
+
+            return Err(MsgError::new("Command stdout is not piped")
+                .context(ErrorKind::CommandOutputRead)
+                .into());
+        }
+    };
+
+    Ok(CommandLines {
+        cmd_line,
+        child,
+        stdout: BufReader::new(stdout),
+        stderr_reader,
+        buf: vec![],
+        done: false,
+        _kind: PhantomData,
+    })
+}
+
+fn redact(buf: &[u8], secret: &[u8]) -> Vec<u8> {
+    // a trailing newline is only there for the prompt, the rest must go
+    let secret = if secret.ends_with(b"\n") {
+        &secret[..secret.len() - 1]
+    } else {
+        secret
+    };
+
+    if secret.is_empty() {
+        return buf.to_vec();
+    }
+
+    let mut redacted = Vec::with_capacity(buf.len());
+    let mut i = 0;
+
+    while i < buf.len() {
+        if buf[i..].starts_with(secret) {
+            redacted.extend_from_slice(REDACTED.as_bytes());
+            i += secret.len();
+        } else {
+            redacted.push(buf[i]);
+            i += 1;
+        }
+    }
+
+    redacted
+}
+
+fn exit_error(
+    cmd_line: String,
+    status: ExitStatus,
+    stderr: &[u8],
+) -> CodeMsgError {
+    let msg = String::from_utf8_lossy(stderr).into_owned();
+
+    CodeMsgError::new(status.code(), msg)
+        .with_cmd(cmd_line)
+        .with_signal(status.signal())
+}
+
+#[allow(deprecated)]
+fn in_own_process_group(cmd: &mut Command) -> &mut Command {
+    // runs in the forked child right before exec
+    unsafe {
+        cmd.before_exec(|| {
+            if libc::setpgid(0, 0) == 0 {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        })
+    }
+}
+
+fn spawn_pipe_reader<R>(mut pipe: R) -> Receiver<io::Result<Vec<u8>>>
+where
+    R: Read + Send + 'static,
+{
+    spawn_with_result(move || {
+        let mut buf = vec![];
+        pipe.read_to_end(&mut buf)?;
+        Ok(buf)
+    })
+}
+
+// the result is sent back over a channel so that waiting for it can time out
+fn spawn_with_result<F, T>(f: F) -> Receiver<io::Result<T>>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let _ = tx.send(f());
+    });
+
+    rx
+}
+
+fn join_thread<T>(
+    result: Option<Receiver<io::Result<T>>>,
+) -> io::Result<Option<T>> {
+    join_thread_until(result, None)
+}
+
+// the thread is left behind if it is still running at the deadline
+fn join_thread_until<T>(
+    result: Option<Receiver<io::Result<T>>>,
+    deadline: Option<Instant>,
+) -> io::Result<Option<T>> {
+    let result = match result {
+        Some(result) => result,
+        None => return Ok(None),
+    };
+
+    let res = match deadline {
+        Some(deadline) => {
+            let now = Instant::now();
+
+            let timeout = if deadline > now {
+                deadline - now
+            } else {
+                Duration::from_secs(0)
+            };
+
+            result.recv_timeout(timeout).map_err(|e| match e {
+                RecvTimeoutError::Timeout => io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "Command pipe still open after the timeout",
+                ),
+                RecvTimeoutError::Disconnected => thread_panicked(),
+            })?
+        }
+        None => result.recv().map_err(|_| thread_panicked())?,
+    };
+
+    res.map(Some)
+}
+
+fn thread_panicked() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "Command pipe thread panicked")
+}
+
+// returns None if the timeout elapsed and the process group was killed
+fn wait_child(
+    child: &mut Child,
+    timeout: Option<Duration>,
+) -> io::Result<Option<ExitStatus>> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return child.wait().map(Some),
+    };
+
+    let start = Instant::now();
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+
+        let elapsed = start.elapsed();
+
+        if elapsed >= timeout {
+            kill_process_group(child.id());
+            child.wait()?;
+            return Ok(None);
+        }
+
+        let poll_interval = Duration::from_millis(COMMAND_POLL_INTERVAL_MS);
+        thread::sleep(cmp::min(poll_interval, timeout - elapsed));
+    }
+}
+
+fn kill_process_group(pgid: u32) {
+    // negative pid signals every process in the group
+    unsafe {
+        libc::kill(-(pgid as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+pub type LineStream<K> = Box<Iterator<Item = Result<String, Error<K>>> + Send>;
+
+pub trait CommandExecutor {
+    fn run<K, S>(
+        &self,
+        program: &str,
+        args: &[S],
+        opts: &CommandOptions,
+    ) -> Result<CommandOutput, Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+        S: AsRef<str>;
+
+    fn run_with_secret<K, S>(
+        &self,
+        program: &str,
+        args: &[S],
+        secret: Secret,
+        opts: &CommandOptions,
+    ) -> Result<CommandOutput, Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+        S: AsRef<str>;
+
+    fn stream_lines<K, S>(
+        &self,
+        program: &str,
+        args: &[S],
+    ) -> Result<LineStream<K>, Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+        S: AsRef<str>;
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+pub struct SystemExecutor;
+
+impl CommandExecutor for SystemExecutor {
+    fn run<K, S>(
+        &self,
+        program: &str,
+        args: &[S],
+        opts: &CommandOptions,
+    ) -> Result<CommandOutput, Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+        S: AsRef<str>,
+    {
+        run_command(
+            Command::new(program).args(args.iter().map(|arg| arg.as_ref())),
+            opts,
+        )
+    }
+
+    fn run_with_secret<K, S>(
+        &self,
+        program: &str,
+        args: &[S],
+        secret: Secret,
+        opts: &CommandOptions,
+    ) -> Result<CommandOutput, Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+        S: AsRef<str>,
+    {
+        run_command_with_secret(
+            Command::new(program).args(args.iter().map(|arg| arg.as_ref())),
+            secret,
+            opts,
+        )
+    }
+
+    fn stream_lines<K, S>(
+        &self,
+        program: &str,
+        args: &[S],
+    ) -> Result<LineStream<K>, Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+        S: AsRef<str>,
+    {
+        let lines = stream_command_lines(
+            Command::new(program).args(args.iter().map(|arg| arg.as_ref())),
+        )?;
+
+        Ok(Box::new(lines))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptedResponse {
+    pub program: String,
+    // None matches any arguments
+    pub args: Option<Vec<String>>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub code: i32,
+}
+
+impl ScriptedResponse {
+    pub fn new<P, S>(program: P, args: &[S]) -> ScriptedResponse
+    where
+        P: Into<String>,
+        S: AsRef<str>,
+    {
+        let args = args.iter().map(|arg| arg.as_ref().to_owned()).collect();
+        ScriptedResponse::with_args(program, Some(args))
+    }
+
+    pub fn any_args<P>(program: P) -> ScriptedResponse
+    where
+        P: Into<String>,
+    {
+        ScriptedResponse::with_args(program, None)
+    }
+
+    fn with_args<P>(program: P, args: Option<Vec<String>>) -> ScriptedResponse
+    where
+        P: Into<String>,
+    {
+        ScriptedResponse {
+            program: program.into(),
+            args,
+            stdout: vec![],
+            stderr: vec![],
+            code: 0,
+        }
+    }
+
+    pub fn stdout<B>(mut self, stdout: B) -> ScriptedResponse
+    where
+        B: Into<Vec<u8>>,
+    {
+        self.stdout = stdout.into();
+        self
+    }
+
+    pub fn stderr<B>(mut self, stderr: B) -> ScriptedResponse
+    where
+        B: Into<Vec<u8>>,
+    {
+        self.stderr = stderr.into();
+        self
+    }
+
+    pub fn code(mut self, code: i32) -> ScriptedResponse {
+        self.code = code;
+        self
+    }
+
+    fn matches(&self, program: &str, args: &[String]) -> bool {
+        if self.program != program {
+            return false;
+        }
+
+        match self.args {
+            Some(ref expected) => expected.as_slice() == args,
+            None => true,
+        }
+    }
+
+    fn output(&self) -> CommandOutput {
+        CommandOutput {
+            // wait status layout, exit code lives in the second byte
+            status: ExitStatus::from_raw((self.code & 0xff) << 8),
+            stdout: self.stdout.clone(),
+            stderr: self.stderr.clone(),
+            elapsed: Duration::from_secs(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptedCall {
+    pub program: String,
+    pub args: Vec<String>,
+    pub stdin: Option<Vec<u8>>,
+}
+
+#[derive(Default, Debug)]
+pub struct ScriptedExecutor {
+    responses: Vec<ScriptedResponse>,
+    // behind a mutex so that the fake can be shared by parallel callers
+    calls: Mutex<Vec<ScriptedCall>>,
+}
+
+impl ScriptedExecutor {
+    pub fn new() -> ScriptedExecutor {
+        ScriptedExecutor::default()
+    }
+
+    pub fn respond(mut self, response: ScriptedResponse) -> ScriptedExecutor {
+        self.responses.push(response);
+        self
+    }
+
+    pub fn calls(&self) -> Vec<ScriptedCall> {
+        match self.calls.lock() {
+            Ok(calls) => calls.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    fn call<K, S>(
+        &self,
+        program: &str,
+        args: &[S],
+        stdin: Option<Vec<u8>>,
+    ) -> Result<(String, CommandOutput), Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+        S: AsRef<str>,
+    {
+        let args: Vec<String> =
+            args.iter().map(|arg| arg.as_ref().to_owned()).collect();
+
+        let cmd_line = command_line(Command::new(program).args(&args));
+
+        {
+            let mut calls = match self.calls.lock() {
+                Ok(calls) => calls,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+
+            calls.push(ScriptedCall {
+                program: program.to_owned(),
+                args: args.clone(),
+                stdin,
+            });
+        }
+
+        // first scripted match wins, same as a real lookup failing to spawn
+        let response = self
+            .responses
+            .iter()
+            .find(|r| r.matches(program, &args))
+            .ok_or_else(|| {
+                CodeMsgError::new(None, "No scripted response for command")
+                    .with_cmd(cmd_line.clone())
+            })
+            .context(ErrorKind::CommandSpawn)?;
+
+        Ok((cmd_line, response.output()))
+    }
+}
+
+impl CommandExecutor for ScriptedExecutor {
+    fn run<K, S>(
+        &self,
+        program: &str,
+        args: &[S],
+        opts: &CommandOptions,
+    ) -> Result<CommandOutput, Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+        S: AsRef<str>,
+    {
+        let (cmd_line, output) =
+            self.call(program, args, opts.stdin.clone())?;
+
+        if !output.status.success() {
+            Err(exit_error(cmd_line, output.status, &output.stderr))
+                .context(ErrorKind::ChildOutput)?;
+        }
+
+        Ok(output)
+    }
+
+    fn run_with_secret<K, S>(
+        &self,
+        program: &str,
+        args: &[S],
+        secret: Secret,
+        _opts: &CommandOptions,
+    ) -> Result<CommandOutput, Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+        S: AsRef<str>,
+    {
+        // only the shape of the input is recorded, never the secret itself
+        let stdin = redact(secret.as_bytes(), secret.as_bytes());
+        let (cmd_line, output) = self.call(program, args, Some(stdin))?;
+
+        if !output.status.success() {
+            let stderr = redact(&output.stderr, secret.as_bytes());
+
+            Err(exit_error(cmd_line, output.status, &stderr))
+                .context(ErrorKind::ChildOutput)?;
+        }
+
+        Ok(output)
+    }
+
+    fn stream_lines<K, S>(
+        &self,
+        program: &str,
+        args: &[S],
+    ) -> Result<LineStream<K>, Error<K>>
+    where
+        K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+        S: AsRef<str>,
+    {
+        let (cmd_line, output) = self.call(program, args, None)?;
+
+        let mut lines: Vec<Result<String, Error<K>>> =
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(|line| Ok(line.to_owned()))
+                .collect();
+
+        if !output.status.success() {
+            let err = exit_error(cmd_line, output.status, &output.stderr);
+            lines.push(Err(err.context(ErrorKind::ChildOutput).into()));
+        }
+
+        Ok(Box::new(lines.into_iter()))
+    }
+}
+
+pub fn which(program: &str) -> Result<PathBuf, ProgramNotFoundError> {
+    which_in(program, &[] as &[&str])
+}
+
+pub fn which_in<P>(
+    program: &str,
+    extra_dirs: &[P],
+) -> Result<PathBuf, ProgramNotFoundError>
+where
+    P: AsRef<Path>,
+{
+    // anything with a slash is taken as a path and not searched for
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+
+        return if is_executable(&path) {
+            Ok(path)
+        } else {
+            Err(ProgramNotFoundError::new(program, vec![]))
+        };
+    }
+
+    let mut dirs: Vec<PathBuf> = extra_dirs
+        .iter()
+        .map(|dir| dir.as_ref().to_owned())
+        .collect();
+
+    if let Some(path_var) = env::var_os("PATH") {
+        dirs.extend(
+            env::split_paths(&path_var)
+                .filter(|dir| !dir.as_os_str().is_empty()),
+        );
+    }
+
+    // the searched dirs are part of the key so PATH changes are respected
+    let key = (program.to_owned(), dirs);
+
+    if let Ok(cache) = WHICH_CACHE.lock() {
+        if let Some(path) = cache.get(&key) {
+            return Ok(path.clone());
+        }
+    }
+
+    let found = key
+        .1
+        .iter()
+        .map(|dir| dir.join(program))
+        .find(|path| is_executable(path));
+
+    match found {
+        Some(path) => {
+            if let Ok(mut cache) = WHICH_CACHE.lock() {
+                cache.insert(key, path.clone());
+            }
+
+            Ok(path)
+        }
+        None => Err(ProgramNotFoundError::new(key.0, key.1)),
+    }
+}
+
+pub fn hadoop_home_bin() -> Option<PathBuf> {
+    env::var_os("HADOOP_HOME").map(|home| PathBuf::from(home).join("bin"))
+}
+
+pub fn require_hdfs<P, K>(extra_dirs: &[P]) -> Result<PathBuf, Error<K>>
+where
+    P: AsRef<Path>,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let mut dirs: Vec<PathBuf> = extra_dirs
+        .iter()
+        .map(|dir| dir.as_ref().to_owned())
+        .collect();
+
+    dirs.extend(hadoop_home_bin());
+
+    let path = which_in("hdfs", &dirs).context(ErrorKind::HdfsNotAvailable)?;
+    Ok(path)
+}
+
+pub fn require_kinit<P, K>(extra_dirs: &[P]) -> Result<PathBuf, Error<K>>
+where
+    P: AsRef<Path>,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    let path =
+        which_in("kinit", extra_dirs).context(ErrorKind::KinitNotAvailable)?;
+
+    Ok(path)
+}
+
+// external programs that an app can ask to have checked at startup
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Program {
+    Hdfs,
+    Kinit,
+}
+
+pub fn require_programs<P, K>(
+    programs: &[Program],
+    extra_dirs: &[P],
+) -> Result<(), Error<K>>
+where
+    P: AsRef<Path>,
+    K: From<ErrorKind> + Copy + Clone + Eq + PartialEq + Debug + Fail,
+{
+    for program in programs {
+        let path = match *program {
+            Program::Hdfs => require_hdfs(extra_dirs)?,
+            Program::Kinit => require_kinit(extra_dirs)?,
+        };
+
+        debug!("Found {:?} at {:?}", program, path);
+    }
+
+    Ok(())
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use error;
+    use util::test_util::TempDir;
+
+    #[test]
+    fn test_process_started() {
+        let started = process_started(::std::process::id());
+        assert!(started.is_some());
+
+        let started = started.unwrap();
+        assert!(started <= Local::now());
+        assert!(started > Local::now() - ::chrono::Duration::hours(1));
+
+        assert!(process_started(std::u32::MAX).is_none());
+    }
+
+    #[test]
+    fn test_run_command_stdin_stdout_stderr() {
+        let output: error::Result<CommandOutput> = run_command(
+            Command::new("sh").args(&["-c", "cat; echo err >&2"]),
+            &CommandOptions::default().stdin("payload"),
+        );
+
+        assert!(output.is_ok());
+        let output = output.unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(b"err\n".to_vec(), output.stderr);
+
+        let stdout: error::Result<String> = output.into_stdout_str();
+        assert!(stdout.is_ok());
+        assert_eq!("payload", stdout.unwrap());
+    }
+
+    #[test]
+    fn test_run_command_large_stderr() {
+        // stderr beyond the pipe buffer size must not block the child
+        let output: error::Result<CommandOutput> = run_command(
+            Command::new("sh")
+                .args(&["-c", "head -c 200000 /dev/zero >&2; echo done"]),
+            &CommandOptions::default().timeout(Duration::from_secs(10)),
+        );
+
+        assert!(output.is_ok());
+        let output = output.unwrap();
+
+        assert_eq!(200000, output.stderr.len());
+        assert_eq!(b"done\n".to_vec(), output.stdout);
+    }
+
+    #[test]
+    fn test_run_command_non_zero_exit() {
+        let output: error::Result<CommandOutput> = run_command(
+            Command::new("sh").args(&["-c", "echo boom >&2; exit 3"]),
+            &CommandOptions::default(),
+        );
+
+        assert!(output.is_err());
+        let err = output.unwrap_err();
+
+        assert_eq!(ErrorKind::ChildOutput, *err.inner.get_context());
+
+        let msg = err.to_string();
+        assert!(msg.contains("code: Some(3)"));
+        assert!(msg.contains("boom"));
+        assert!(msg.contains("\"sh\""));
+    }
+
+    #[test]
+    fn test_run_command_timeout() {
+        let start = Instant::now();
+
+        // the background sleep shares the process group and is killed too
+        let output: error::Result<CommandOutput> = run_command(
+            Command::new("sh").args(&["-c", "sleep 10 & sleep 10"]),
+            &CommandOptions::default().timeout(Duration::from_millis(200)),
+        );
+
+        assert!(output.is_err());
+        assert_eq!(
+            ErrorKind::CommandTimeout,
+            *output.unwrap_err().inner.get_context()
+        );
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_run_command_timeout_escaped_descendant() {
+        let start = Instant::now();
+
+        // the setsid sleep leaves the process group, survives the kill and
+        // keeps stdout and stderr open
+        let output: error::Result<CommandOutput> = run_command(
+            Command::new("sh").args(&["-c", "setsid sleep 10 & sleep 10"]),
+            &CommandOptions::default().timeout(Duration::from_millis(200)),
+        );
+
+        assert!(output.is_err());
+        assert_eq!(
+            ErrorKind::CommandTimeout,
+            *output.unwrap_err().inner.get_context()
+        );
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_run_command_spawn_error() {
+        let output: error::Result<CommandOutput> = run_command(
+            &mut Command::new("/mega_coll/not/exist"),
+            &CommandOptions::default(),
+        );
+
+        assert!(output.is_err());
+        assert_eq!(
+            ErrorKind::CommandSpawn,
+            *output.unwrap_err().inner.get_context()
+        );
+    }
+
+    #[test]
+    fn test_stream_command_lines() {
+        let lines: error::Result<CommandLines<ErrorKind>> =
+            stream_command_lines(
+                Command::new("sh").args(&["-c", "printf 'a\\nb\\r\\nc'"]),
+            );
+
+        assert!(lines.is_ok());
+
+        let lines: error::Result<Vec<String>> = lines.unwrap().collect();
+        assert!(lines.is_ok());
+        assert_eq!(vec!["a", "b", "c"], lines.unwrap());
+    }
+
+    #[test]
+    fn test_stream_command_lines_many() {
+        let lines: error::Result<CommandLines<ErrorKind>> =
+            stream_command_lines(Command::new("seq").args(&["1", "100000"]));
+
+        assert!(lines.is_ok());
+
+        let mut count = 0;
+
+        for line in lines.unwrap() {
+            assert!(line.is_ok());
+            count += 1;
+        }
+
+        assert_eq!(100000, count);
+    }
+
+    #[test]
+    fn test_stream_command_lines_non_zero_exit() {
+        let lines: error::Result<CommandLines<ErrorKind>> =
+            stream_command_lines(
+                Command::new("sh")
+                    .args(&["-c", "echo a; echo boom >&2; exit 2"]),
+            );
+
+        assert!(lines.is_ok());
+        let mut lines = lines.unwrap();
+
+        assert_eq!("a", lines.next().unwrap().unwrap());
+
+        let err = lines.next().unwrap().unwrap_err();
+        assert_eq!(ErrorKind::ChildOutput, *err.inner.get_context());
+        assert!(err.to_string().contains("boom"));
+
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn test_stream_command_lines_early_drop() {
+        let lines: error::Result<CommandLines<ErrorKind>> =
+            stream_command_lines(&mut Command::new("yes"));
+
+        assert!(lines.is_ok());
+
+        // dropping the iterator must kill the never ending child
+        let taken: Vec<_> = lines.unwrap().take(3).collect();
+        assert_eq!(3, taken.len());
+    }
+
+    #[test]
+    fn test_system_executor() {
+        let output: error::Result<CommandOutput> = SystemExecutor.run(
+            "sh",
+            &["-c", "echo out"],
+            &CommandOptions::default(),
+        );
+
+        assert!(output.is_ok());
+        assert_eq!(b"out\n".to_vec(), output.unwrap().stdout);
+
+        let lines: error::Result<Vec<String>> = SystemExecutor
+            .stream_lines("sh", &["-c", "echo a; echo b"])
+            .and_then(|lines| lines.collect());
+
+        assert!(lines.is_ok());
+        assert_eq!(vec!["a", "b"], lines.unwrap());
+    }
+
+    #[test]
+    fn test_scripted_executor() {
+        let executor = ScriptedExecutor::new()
+            .respond(
+                ScriptedResponse::new("hdfs", &["dfs", "-ls", "/"])
+                    .stdout("a\nb\n"),
+            )
+            .respond(
+                ScriptedResponse::any_args("kinit")
+                    .stderr("bad password")
+                    .code(1),
+            );
+
+        let output: error::Result<CommandOutput> = executor.run(
+            "hdfs",
+            &["dfs", "-ls", "/"],
+            &CommandOptions::default(),
+        );
+
+        assert!(output.is_ok());
+        let output = output.unwrap();
+        assert!(output.status.success());
+        assert_eq!(b"a\nb\n".to_vec(), output.stdout);
+
+        let output: error::Result<CommandOutput> = executor.run(
+            "kinit",
+            &["user"],
+            &CommandOptions::default().stdin("pw"),
+        );
+
+        assert!(output.is_err());
+        let err = output.unwrap_err();
+        assert_eq!(ErrorKind::ChildOutput, *err.inner.get_context());
+        assert!(err.to_string().contains("code: Some(1)"));
+        assert!(err.to_string().contains("bad password"));
+
+        // args differ from the scripted ones
+        let output: error::Result<CommandOutput> = executor.run(
+            "hdfs",
+            &["dfs", "-ls", "/other"],
+            &CommandOptions::default(),
+        );
+
+        assert!(output.is_err());
+        assert_eq!(
+            ErrorKind::CommandSpawn,
+            *output.unwrap_err().inner.get_context()
+        );
+
+        let calls = executor.calls();
+        assert_eq!(3, calls.len());
+        assert_eq!("kinit", calls[1].program);
+        assert_eq!(Some(b"pw".to_vec()), calls[1].stdin);
+    }
+
+    #[test]
+    fn test_scripted_executor_stream_lines() {
+        let executor = ScriptedExecutor::new().respond(
+            ScriptedResponse::any_args("hdfs")
+                .stdout("a\nb\n")
+                .stderr("partial")
+                .code(1),
+        );
+
+        let lines: error::Result<LineStream<ErrorKind>> =
+            executor.stream_lines("hdfs", &["dfs", "-ls", "-R", "/"]);
+
+        assert!(lines.is_ok());
+        let lines = lines.unwrap();
+
+        // a stream can be handed to another thread
+        let lines = thread::spawn(move || {
+            lines.collect::<Vec<error::Result<String>>>()
+        })
+        .join();
+
+        assert!(lines.is_ok());
+        let lines = lines.unwrap();
+
+        assert_eq!(3, lines.len());
+        assert_eq!("b", lines[1].as_ref().unwrap());
+        assert!(lines[2].is_err());
+    }
+
+    #[test]
+    fn test_which_in() {
+        let dir = TempDir::new("which");
+
+        let tool = dir.join("mega_coll_fake_tool");
+        let no_exec = dir.join("mega_coll_fake_no_exec");
+        assert!(fs::write(&tool, "#!/bin/sh\n").is_ok());
+        assert!(fs::write(&no_exec, "#!/bin/sh\n").is_ok());
+
+        assert!(
+            fs::set_permissions(&tool, fs::Permissions::from_mode(0o755))
+                .is_ok()
+        );
+
+        assert!(fs::set_permissions(
+            &no_exec,
+            fs::Permissions::from_mode(0o644)
+        )
+        .is_ok());
+
+        let found = which_in("mega_coll_fake_tool", &[&dir]);
+        assert!(found.is_ok());
+        assert_eq!(tool, found.unwrap());
+
+        // cached lookups give the same answer
+        assert_eq!(tool, which_in("mega_coll_fake_tool", &[&dir]).unwrap());
+
+        let not_found = which_in("mega_coll_fake_no_exec", &[&dir]);
+        assert!(not_found.is_err());
+
+        let err = not_found.unwrap_err();
+        assert_eq!("mega_coll_fake_no_exec", err.program);
+        assert_eq!(dir.to_path_buf(), err.searched[0]);
+
+        let by_path = which_in(tool.to_str().unwrap(), &[] as &[&str]);
+        assert!(by_path.is_ok());
+    }
+
+    #[test]
+    fn test_require_programs() {
+        let dir = TempDir::new("require_programs");
+
+        for program in &["hdfs", "kinit"] {
+            let path = dir.join(program);
+            assert!(fs::write(&path, "#!/bin/sh\n").is_ok());
+
+            assert!(fs::set_permissions(
+                &path,
+                fs::Permissions::from_mode(0o755)
+            )
+            .is_ok());
+        }
+
+        let required: error::Result<()> =
+            require_programs(&[Program::Hdfs, Program::Kinit], &[&dir]);
+
+        assert!(required.is_ok());
+    }
+
+    #[test]
+    fn test_which_path() {
+        assert!(which("sh").is_ok());
+        assert!(which("mega_coll_fake_not_exist").is_err());
+    }
+
+    #[test]
+    fn test_secret_line_debug() {
+        let secret = Secret::line("hunter2");
+        assert_eq!(b"hunter2\n", secret.as_bytes());
+        assert_eq!("Secret(<redacted>)", format!("{:?}", secret));
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            b"bad <redacted> and <redacted>!".to_vec(),
+            redact(b"bad hunter2 and hunter2!", b"hunter2\n")
+        );
+
+        assert_eq!(b"as is".to_vec(), redact(b"as is", b""));
+    }
+
+    #[test]
+    fn test_run_command_with_secret() {
+        let output: error::Result<CommandOutput> = run_command_with_secret(
+            Command::new("sh").args(&["-c", "read pw; test \"$pw\" = hunter2"]),
+            Secret::line("hunter2"),
+            &CommandOptions::default(),
+        );
+
+        assert!(output.is_ok());
+
+        // a child that echoes the secret back must not leak it into errors
+        let output: error::Result<CommandOutput> = run_command_with_secret(
+            Command::new("sh")
+                .args(&["-c", "read pw; echo \"bad $pw\" >&2; exit 1"]),
+            Secret::line("hunter2"),
+            &CommandOptions::default(),
+        );
+
+        assert!(output.is_err());
+
+        let msg = output.unwrap_err().to_string();
+        assert!(!msg.contains("hunter2"));
+        assert!(msg.contains("bad <redacted>"));
+    }
+}
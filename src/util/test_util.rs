@@ -0,0 +1,44 @@
+use std::env;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+
+// removes the directory and everything in it even if the test fails
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    // unique to the test name and process, always starts out empty
+    pub fn new(name: &str) -> TempDir {
+        let dir = env::temp_dir().join(format!(
+            "mega_coll_test_{}_{}",
+            name,
+            process::id()
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+        assert!(fs::create_dir_all(&dir).is_ok());
+        TempDir(dir)
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}